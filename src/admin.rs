@@ -0,0 +1,158 @@
+use axum::Router;
+use axum::extract::{Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::file_handler::FileHandler;
+use crate::runner::Runner;
+
+#[derive(Clone)]
+struct AdminState {
+    runner: Arc<Runner>,
+    config_path: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct FileQuery {
+    /// SQL文件相对于start_dir的路径，支持glob，如`subdir/*.sql`
+    file: String,
+}
+
+/// 管理接口，挂在`/_admin`下，令牌在每次请求时从当前配置中读取
+pub fn router(runner: Arc<Runner>, config_path: PathBuf) -> Result<Router, Box<dyn std::error::Error>> {
+    if runner.config().admin.as_ref().is_some_and(|admin| admin.token.is_empty()) {
+        return Err("[admin] token must not be empty".into());
+    }
+    
+    let state = AdminState { runner, config_path };
+    Ok(Router::new()
+        .route("/status", get(status))
+        .route("/history", get(history))
+        .route("/refresh", post(refresh))
+        .route("/pause", post(pause))
+        .route("/resume", post(resume))
+        .route("/reload", post(reload))
+        .layer(middleware::from_fn_with_state(state.clone(), require_token))
+        .with_state(state))
+}
+
+async fn require_token(State(state): State<AdminState>, request: Request, next: Next) -> Response {
+    let expected = state.runner.config().admin.as_ref().map(|admin| admin.token.expose().to_string());
+    let authorized = request.headers().get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .zip(expected)
+        .is_some_and(|(token, expected)| token_matches(token.trim(), &expected));
+    if !authorized {
+        let mut response = error_response(StatusCode::UNAUTHORIZED, "unauthorized");
+        response.headers_mut().insert(header::WWW_AUTHENTICATE, "Bearer".parse().unwrap());
+        return response;
+    }
+    next.run(request).await
+}
+
+/// 比较两个令牌的哈希，避免按字符提前返回泄露令牌前缀
+pub fn token_matches(token: &str, expected: &str) -> bool {
+    !expected.is_empty()
+        && FileHandler::content_hash(token.as_bytes()) == FileHandler::content_hash(expected.as_bytes())
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    (status, axum::Json(json!({ "error": message }))).into_response()
+}
+
+async fn status(State(state): State<AdminState>) -> Response {
+    let runner = state.runner.clone();
+    match tokio::task::spawn_blocking(move || runner.status()).await {
+        Ok(status) => axum::Json(status).into_response(),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    }
+}
+
+async fn history(State(state): State<AdminState>, Query(query): Query<FileQuery>) -> Response {
+    let files = match matching_files(&state.runner, &query.file) {
+        Ok(files) => files,
+        Err((status, message)) => return error_response(status, &message),
+    };
+    let history: BTreeMap<String, _> = files.iter()
+        .map(|sql_file| (state.runner.relative_path(sql_file), state.runner.history(sql_file)))
+        .collect();
+    axum::Json(history).into_response()
+}
+
+/// 立即重新生成匹配的文件，不检查更新间隔和暂停状态，返回与运行报告相同格式的结果
+async fn refresh(State(state): State<AdminState>, Query(query): Query<FileQuery>) -> Response {
+    let files = match matching_files(&state.runner, &query.file) {
+        Ok(files) => files,
+        Err((status, message)) => return error_response(status, &message),
+    };
+    
+    let runner = state.runner.clone();
+    let results = tokio::task::spawn_blocking(move || {
+        files.iter().map(|sql_file| {
+            log::info!("Refreshing file {} (admin request)", sql_file.display());
+            runner.run_file(sql_file, true)
+        }).collect::<Vec<_>>()
+    }).await;
+    
+    match results {
+        Ok(results) => axum::Json(results).into_response(),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    }
+}
+
+async fn pause(State(state): State<AdminState>, Query(query): Query<FileQuery>) -> Response {
+    set_paused(&state.runner, &query.file, true)
+}
+
+async fn resume(State(state): State<AdminState>, Query(query): Query<FileQuery>) -> Response {
+    set_paused(&state.runner, &query.file, false)
+}
+
+fn set_paused(runner: &Runner, pattern: &str, paused: bool) -> Response {
+    let files = match matching_files(runner, pattern) {
+        Ok(files) => files,
+        Err((status, message)) => return error_response(status, &message),
+    };
+    let paths: Vec<String> = files.iter().map(|sql_file| {
+        runner.set_paused(sql_file, paused);
+        runner.relative_path(sql_file)
+    }).collect();
+    log::info!("{} {} file(s) matching {}", if paused { "Paused" } else { "Resumed" }, paths.len(), pattern);
+    axum::Json(json!({ "paused": paused, "files": paths })).into_response()
+}
+
+/// 重新读取配置文件，数据库连接、状态文件和监听地址需要重启才会生效
+async fn reload(State(state): State<AdminState>) -> Response {
+    let runner = state.runner.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        Config::from_file(&state.config_path)
+            .and_then(|config| runner.reload(config))
+            .map_err(|e| e.to_string())
+    }).await;
+    
+    match result {
+        Ok(Ok(())) => axum::Json(json!({ "reloaded": true })).into_response(),
+        Ok(Err(e)) => {
+            log::error!("Failed to reload configuration: {}", e);
+            error_response(StatusCode::BAD_REQUEST, &e)
+        }
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    }
+}
+
+fn matching_files(runner: &Runner, pattern: &str) -> Result<Vec<PathBuf>, (StatusCode, String)> {
+    match runner.matching_files(pattern) {
+        Ok(files) if files.is_empty() => Err((StatusCode::NOT_FOUND, format!("no SQL file matches '{}'", pattern))),
+        Ok(files) => Ok(files),
+        Err(e) => Err((StatusCode::BAD_REQUEST, e.to_string())),
+    }
+}
@@ -0,0 +1,83 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::{BackendKind, DatabaseConfig};
+use crate::db::DbPool;
+
+/// 与数据库无关的列值
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(not(feature = "postgres"), allow(dead_code))]
+pub enum SqlValue {
+    /// NULL
+    Null,
+    /// 布尔值
+    Bool(bool),
+    /// 有符号整数
+    Int(i64),
+    /// 超出i64范围的无符号整数
+    UInt(u64),
+    /// 浮点数
+    Float(f64),
+    /// DECIMAL/NUMERIC，以字符串保存避免精度损失
+    Decimal(String),
+    /// 字符串，日期和时间也按数据库的文本格式保存
+    Text(String),
+    /// 二进制数据
+    Bytes(Vec<u8>),
+    /// JSON列
+    Json(serde_json::Value),
+}
+
+/// 一条语句的结果集
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResultSet {
+    /// 列名，按SELECT中的顺序
+    pub columns: Vec<String>,
+    /// 每行的值，与`columns`一一对应
+    pub rows: Vec<Vec<SqlValue>>,
+}
+
+/// 执行SQL文件的数据库连接（通常是连接池）
+pub trait Backend: Send + Sync {
+    /// 执行SQL，返回各语句的结果集；`read_only`时在只读事务（或只读连接）中执行
+    fn query(&self, sql: &str) -> Result<Vec<ResultSet>, Box<dyn std::error::Error>>;
+    
+    /// 数据库版本，启动时写入日志
+    fn version(&self) -> Result<String, Box<dyn std::error::Error>>;
+    
+    /// 是否在只读事务中执行
+    fn read_only(&self) -> bool;
+    
+    /// 当前从连接池取出、尚未归还的连接数
+    fn connections_in_use(&self) -> usize {
+        0
+    }
+    
+    /// 副本的复制延迟（秒），复制未运行时返回None
+    fn replication_lag(&self, _heartbeat_table: Option<&str>) -> Result<Option<f64>, Box<dyn std::error::Error>> {
+        Err("replication lag checks are not supported by this backend".into())
+    }
+    
+    /// MySQL连接池，命名锁和`mysql`输出需要
+    fn as_mysql(&self) -> Option<&DbPool> {
+        None
+    }
+}
+
+/// 按`backend`创建连接，`lazy`时不在启动时建立连接（用于副本）
+pub fn connect(config: &DatabaseConfig, lazy: bool) -> Result<Arc<dyn Backend>, Box<dyn std::error::Error>> {
+    match config.backend {
+        BackendKind::Mysql if lazy => Ok(Arc::new(DbPool::new_lazy(config, 10)?)),
+        BackendKind::Mysql => Ok(Arc::new(DbPool::new(
+            config,
+            10, // 最大连接数
+            Duration::from_secs(30) // 超时时间
+        )?)),
+        #[cfg(feature = "postgres")]
+        BackendKind::Postgres => Ok(Arc::new(crate::postgres_backend::PostgresBackend::new(config)?)),
+        #[cfg(feature = "sqlite")]
+        BackendKind::Sqlite => Ok(Arc::new(crate::sqlite_backend::SqliteBackend::open(config)?)),
+        #[allow(unreachable_patterns)]
+        kind => Err(format!("backend {:?} requires building with the {:?} feature", kind, kind).into()),
+    }
+}
@@ -0,0 +1,136 @@
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, OnceLock, RwLock};
+use tokio::sync::broadcast;
+
+use crate::file_handler::{FileHandler, Precompressed};
+
+/// 内存中的一份JSON结果
+pub struct Payload {
+    /// JSON内容
+    pub body: Bytes,
+    /// 带引号的强ETag（内容的SHA-256）
+    pub etag: String,
+    /// 内容最后一次变化的时间
+    pub last_modified: DateTime<Utc>,
+    /// 响应的Cache-Control，为空时不发送
+    pub cache_control: Option<String>,
+    /// 预压缩的gzip内容
+    pub gzip: Option<Bytes>,
+    /// 预压缩的brotli内容
+    pub brotli: Option<Bytes>,
+    parsed: OnceLock<Option<serde_json::Value>>,
+}
+
+impl Payload {
+    /// 解析后的JSON，第一次带查询参数的请求时解析，之后复用
+    pub fn parsed(&self) -> Option<&serde_json::Value> {
+        self.parsed.get_or_init(|| serde_json::from_slice(&self.body).ok()).as_ref()
+    }
+}
+
+/// 某个URL路径的内容发生了变化
+#[derive(Clone)]
+pub struct ChangeEvent {
+    /// 发生变化的URL路径
+    pub url_path: String,
+    /// 变化后的内容
+    pub payload: Arc<Payload>,
+}
+
+/// 按URL路径保存最新的JSON结果，更新时整体替换，读取方不会看到写了一半的内容
+pub struct PayloadStore {
+    entries: RwLock<HashMap<String, Arc<Payload>>>,
+    changes: broadcast::Sender<ChangeEvent>,
+}
+
+impl Default for PayloadStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PayloadStore {
+    /// 创建空的存储
+    pub fn new() -> Self {
+        PayloadStore {
+            entries: RwLock::new(HashMap::new()),
+            changes: broadcast::channel(64).0,
+        }
+    }
+    
+    /// 订阅内容变化，接收方处理太慢时会丢失较早的事件
+    pub fn subscribe(&self) -> broadcast::Receiver<ChangeEvent> {
+        self.changes.subscribe()
+    }
+    
+    /// 某个URL路径的最新内容
+    pub fn get(&self, url_path: &str) -> Option<Arc<Payload>> {
+        self.entries.read().unwrap().get(url_path).cloned()
+    }
+    
+    /// 某个URL路径是否已有内容
+    pub fn contains(&self, url_path: &str) -> bool {
+        self.entries.read().unwrap().contains_key(url_path)
+    }
+    
+    /// 发布新内容，内容未变化时保留原来的Last-Modified，变化时通知订阅方
+    pub fn publish(&self, url_path: String, body: Vec<u8>, precompressed: Precompressed, cache_control: Option<String>) {
+        let (payload, changed) = self.insert(url_path.clone(), body, precompressed, Utc::now(), cache_control);
+        if changed {
+            // 没有订阅方时发送失败，可以忽略
+            let _ = self.changes.send(ChangeEvent { url_path, payload });
+        }
+    }
+    
+    /// 从已生成的JSON文件加载，Last-Modified取文件的修改时间
+    ///
+    /// `with_precompressed`为false时不读取`.gz`/`.br`文件，它们可能是以前的配置留下的
+    pub fn load_file(&self, url_path: String, json_path: &Path, cache_control: Option<String>, with_precompressed: bool) -> Result<(), Box<dyn std::error::Error>> {
+        if !json_path.exists() {
+            return Ok(());
+        }
+        let body = fs::read(json_path)?;
+        let modified: DateTime<Utc> = fs::metadata(json_path)?.modified()?.into();
+        let precompressed = if with_precompressed { FileHandler::read_compressed(json_path) } else { Precompressed::default() };
+        self.insert(url_path, body, precompressed, modified, cache_control);
+        Ok(())
+    }
+    
+    /// 返回新内容以及与原来的内容相比是否有变化
+    fn insert(&self, url_path: String, body: Vec<u8>, precompressed: Precompressed, last_modified: DateTime<Utc>, cache_control: Option<String>) -> (Arc<Payload>, bool) {
+        let etag = format!("\"{}\"", FileHandler::content_hash(&body));
+        let mut entries = self.entries.write().unwrap();
+        let (last_modified, changed) = match entries.get(&url_path) {
+            Some(existing) if existing.etag == etag => (existing.last_modified, false),
+            _ => (last_modified, true),
+        };
+        let payload = Arc::new(Payload {
+            body: Bytes::from(body),
+            etag,
+            last_modified,
+            cache_control,
+            gzip: precompressed.gzip.map(Bytes::from),
+            brotli: precompressed.brotli.map(Bytes::from),
+            parsed: OnceLock::new(),
+        });
+        entries.insert(url_path, payload.clone());
+        (payload, changed)
+    }
+    
+    /// SQL文件相对于start_dir的路径，扩展名换成`.json`，如`/subdir/kpi.json`
+    pub fn url_path(sql_file: &Path, start_dir: &Path) -> String {
+        let relative = sql_file.strip_prefix(start_dir).unwrap_or(sql_file);
+        let mut url_path = String::new();
+        for component in relative.with_extension("json").components() {
+            if let std::path::Component::Normal(part) = component {
+                url_path.push('/');
+                url_path.push_str(&part.to_string_lossy());
+            }
+        }
+        url_path
+    }
+}
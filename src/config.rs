@@ -1,37 +1,700 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use toml::from_str;
-use serde::Deserialize;
+use serde::de::{self, DeserializeOwned};
+use serde::{Deserialize, Deserializer, Serialize};
 
+use crate::file_handler::FileHandler;
+
+/// 配置文件的全部内容
 #[derive(Deserialize, Debug, Clone)]
 pub struct Config {
-    pub database: DatabaseConfig,
+    /// 默认连接，用`RunnerBuilder::backend`注入数据库时可以省略
+    pub database: Option<DatabaseConfig>,
+    /// 命名连接，SQL文件可以按指令、`[files]`或路径选择
+    pub databases: Option<HashMap<String, NamedDatabaseConfig>>,
+    /// 通用选项
     pub app: AppConfig,
+    /// 各SQL文件的更新间隔（分钟），键为SQL文件路径
+    #[serde(default, deserialize_with = "lenient_intervals")]
     pub file_intervals: Option<HashMap<String, u64>>,
+    /// 各SQL文件的配置，键为SQL文件路径
+    pub files: Option<HashMap<String, FileConfig>>,
+    /// 内置HTTP服务
+    pub server: Option<ServerConfig>,
+    /// 管理接口
+    pub admin: Option<AdminConfig>,
+    /// 指标输出
+    pub metrics: Option<MetricsConfig>,
+    /// 日志
+    pub log: Option<LogConfig>,
+    /// 预压缩
+    pub compression: Option<CompressionConfig>,
+    /// Redis双写，是名为redis的输出的简写
+    pub redis: Option<RedisConfig>,
+    /// 命名的输出，在`[app].sinks`或`[files]`中按名称引用
+    pub sinks: Option<HashMap<String, SinkConfig>>,
+    /// 脱敏策略
+    pub redaction: Option<RedactionConfig>,
 }
 
+/// 数据库连接，用户名和密码按以下顺序取第一个设置的值：
+/// `user`/`password` > `password_file` > `option_file`
 #[derive(Deserialize, Debug, Clone)]
 pub struct DatabaseConfig {
+    /// 数据库类型，默认为MySQL
+    #[serde(default)]
+    pub backend: BackendKind,
+    /// 服务器地址，默认为localhost
+    #[serde(default = "default_db_host")]
     pub host: String,
-    pub port: u16,
-    pub user: String,
-    pub password: String,
+    /// 默认为MySQL的3306或PostgreSQL的5432
+    #[serde(default, deserialize_with = "lenient_option")]
+    pub port: Option<u16>,
+    /// Unix socket路径，如`/var/run/mysqld/mysqld.sock`，设置后不使用host和port
+    pub socket: Option<String>,
+    /// 用户名
+    pub user: Option<String>,
+    /// 密码
+    pub password: Option<Secret>,
+    /// 从文件读取密码（Docker/Kubernetes secrets），去掉末尾的换行
+    pub password_file: Option<String>,
+    /// MySQL选项文件，如`~/.my.cnf`，读取`[client]`段中的user和password
+    pub option_file: Option<String>,
+    /// 选项文件中覆盖`[client]`的段，如`login_path = "reporting"`读取`[reporting]`
+    pub login_path: Option<String>,
+    /// 数据库名称，SQLite为数据库文件路径（`:memory:`为内存数据库）
     pub database: String,
+    /// SQL文件在只读事务中执行，默认关闭
+    #[serde(default, deserialize_with = "lenient")]
+    pub read_only: bool,
+    /// TLS模式
+    #[serde(default)]
+    pub ssl_mode: SslMode,
+    /// CA证书（PEM或DER），用于verify_ca和verify_identity
+    pub ssl_ca: Option<String>,
+    /// 客户端证书和私钥（PEM或DER，私钥需为RSA），两者需同时设置
+    pub ssl_cert: Option<String>,
+    /// 客户端私钥
+    pub ssl_key: Option<String>,
+    /// 接受无效的服务器证书，仅用于测试
+    #[serde(default, deserialize_with = "lenient")]
+    pub ssl_accept_invalid_certs: bool,
+    /// 只读副本，`read_only`时SQL文件在副本上执行
+    #[serde(default)]
+    pub replicas: Vec<ReplicaConfig>,
+    /// 允许的最大复制延迟（秒），可在`[files]`中按文件覆盖，不设置时不检查
+    #[serde(default, deserialize_with = "lenient_option")]
+    pub max_lag_seconds: Option<f64>,
+    /// pt-heartbeat格式的心跳表（`ts`列为UTC时间），设置后代替`SHOW REPLICA STATUS`测量延迟
+    pub heartbeat_table: Option<String>,
+    /// 所有副本都不可用或延迟过大时的处理方式
+    #[serde(default)]
+    pub lag_fallback: LagFallback,
+}
+
+/// 只读副本，未设置的选项与所属连接相同
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct ReplicaConfig {
+    /// 副本的地址，设置后不使用所属连接的socket
+    pub host: Option<String>,
+    /// 副本的端口
+    #[serde(default, deserialize_with = "lenient_option")]
+    pub port: Option<u16>,
+    /// 副本的Unix socket路径
+    pub socket: Option<String>,
+    /// 副本的用户名
+    pub user: Option<String>,
+    /// 副本的密码
+    pub password: Option<Secret>,
+}
+
+/// 所有副本都不可用或延迟过大时的处理方式
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LagFallback {
+    /// 在主库上执行
+    #[default]
+    Primary,
+    /// 视为失败，保留旧的JSON
+    Fail,
+}
+
+/// 默认连接的名称，`-- @database: default`表示使用`[database]`
+pub const DEFAULT_DATABASE: &str = "default";
+
+/// `[databases.<name>]`，连接选项与`[database]`相同
+#[derive(Deserialize, Debug, Clone)]
+pub struct NamedDatabaseConfig {
+    /// 连接选项
+    #[serde(flatten)]
+    pub connection: DatabaseConfig,
+    /// 使用该连接的SQL文件，相对于start_dir的glob，如`reporting/**`
+    #[serde(default)]
+    pub paths: Vec<String>,
+}
+
+/// 与MySQL客户端的`--ssl-mode`相同
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SslMode {
+    /// 不使用TLS
+    #[default]
+    Disabled,
+    /// 服务器支持时使用TLS，不校验证书
+    Preferred,
+    /// 必须使用TLS，不校验证书
+    Required,
+    /// 必须使用TLS，校验证书由受信任的CA签发
+    VerifyCa,
+    /// 在verify_ca的基础上校验证书中的主机名
+    VerifyIdentity,
+}
+
+fn default_db_host() -> String {
+    "localhost".to_string()
+}
+
+/// 数据库类型
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    /// MySQL
+    #[default]
+    Mysql,
+    /// PostgreSQL
+    Postgres,
+    /// SQLite
+    Sqlite,
+}
+
+/// 数字和布尔类型的选项也接受字符串，如替换环境变量后的`port = "${DB_PORT}"`；解析失败时不在错误中显示值
+fn lenient_value<T: DeserializeOwned + FromStr>(value: toml::Value) -> Result<T, String>
+where
+    T::Err: fmt::Display,
+{
+    match value {
+        toml::Value::String(text) => text.trim().parse().map_err(|e| format!("invalid value: {}", e)),
+        other => T::deserialize(other).map_err(|e| e.to_string()),
+    }
+}
+
+fn lenient<'de, D: Deserializer<'de>, T: DeserializeOwned + FromStr>(deserializer: D) -> Result<T, D::Error>
+where
+    T::Err: fmt::Display,
+{
+    lenient_value(toml::Value::deserialize(deserializer)?).map_err(de::Error::custom)
+}
+
+fn lenient_option<'de, D: Deserializer<'de>, T: DeserializeOwned + FromStr>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T::Err: fmt::Display,
+{
+    lenient(deserializer).map(Some)
+}
+
+fn lenient_intervals<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<HashMap<String, u64>>, D::Error> {
+    HashMap::<String, toml::Value>::deserialize(deserializer)?.into_iter()
+        .map(|(path, value)| lenient_value(value).map(|interval| (path, interval)))
+        .collect::<Result<_, _>>()
+        .map(Some)
+        .map_err(de::Error::custom)
 }
 
+/// `[app]`
 #[derive(Deserialize, Debug, Clone)]
 pub struct AppConfig {
+    /// SQL文件所在的目录
     pub start_dir: String,
+    /// JSON输出根目录，不设置时写在SQL文件旁边
+    pub output_dir: Option<String>,
+    /// 输出文件名模板，默认`{stem}.json`
+    pub filename_template: Option<String>,
+    /// 防止同一个SQL文件被并发执行的锁
+    #[serde(default)]
+    pub lock_backend: LockBackend,
+    /// 默认的输出列表，不设置时为`["file"]`，配置了`[redis]`时加上`"redis"`
+    pub sinks: Option<Vec<String>>,
+    /// 除本地文件外的其他输出（如Redis）失败时的处理方式
+    #[serde(default)]
+    pub sink_policy: SinkPolicy,
+    /// 记录SQL和配置哈希的状态文件，默认放在用户缓存目录下，不写入SQL源码目录和输出目录
+    pub state_file: Option<String>,
+    /// 每轮运行后写入的JSON运行报告，可用`--report`覆盖
+    pub report_file: Option<String>,
+}
+
+/// 防止同一个SQL文件被并发执行的锁
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LockBackend {
+    /// 本机文件锁（`<file>.sql.lock`）
+    #[default]
+    File,
+    /// MySQL `GET_LOCK()`，适用于多台主机共享同一个SQL目录
+    Mysql,
+}
+
+/// 双写策略
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SinkPolicy {
+    /// 所有输出都成功才算成功
+    #[default]
+    All,
+    /// 其他输出失败只记录错误，以本地文件为准
+    BestEffort,
+}
+
+/// 日志格式和日志文件
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct LogConfig {
+    /// 日志格式
+    #[serde(default)]
+    pub format: LogFormat,
+    /// 写入该文件而不是stderr
+    pub file: Option<String>,
+    /// 日志文件超过该大小（MB）时轮转
+    #[serde(default, deserialize_with = "lenient_option")]
+    pub max_size_mb: Option<u64>,
+    /// 按时间轮转（UTC）
+    #[serde(default)]
+    pub rotation: Rotation,
+    /// 保留的轮转文件数
+    #[serde(default = "default_retention", deserialize_with = "lenient")]
+    pub retention: usize,
+}
+
+fn default_retention() -> usize {
+    7
+}
+
+/// 日志格式
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// `[时间] [级别] - 消息`
+    #[default]
+    Text,
+    /// 每行一个JSON对象，带file、phase、duration_ms等字段
+    Json,
+}
+
+/// 日志文件的按时间轮转
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Rotation {
+    /// 不按时间轮转
+    #[default]
+    Never,
+    /// 每小时
+    Hourly,
+    /// 每天
+    Daily,
+}
+
+/// Redis双写
+#[derive(Deserialize, Debug, Clone)]
+pub struct RedisConfig {
+    /// 如`redis://127.0.0.1:6379/0`，可能包含密码
+    pub url: Secret,
+    /// 键名模板，支持`{path}`和`{stem}`
+    #[serde(default = "default_redis_key_template")]
+    pub key_template: String,
+    /// 过期时间（秒），不设置或为0时不过期
+    #[serde(default, deserialize_with = "lenient_option")]
+    pub ttl_seconds: Option<u64>,
+    /// 内容变化时`PUBLISH`通知的频道
+    pub publish_channel: Option<String>,
+    /// 连接和读写超时（秒）
+    #[serde(default = "default_redis_timeout_seconds", deserialize_with = "lenient")]
+    pub timeout_seconds: u64,
+}
+
+fn default_redis_key_template() -> String {
+    "fastsql2json:{path}".to_string()
+}
+
+fn default_redis_timeout_seconds() -> u64 {
+    5
+}
+
+/// 输出配置，`type`为redis、s3、webhook或mysql
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SinkConfig {
+    /// Redis
+    Redis(RedisConfig),
+    /// S3兼容的对象存储
+    S3(S3Config),
+    /// HTTP回调
+    Webhook(WebhookConfig),
+    /// MySQL缓存表
+    Mysql(MysqlSinkConfig),
+}
+
+/// S3兼容的对象存储（AWS S3、MinIO等）
+#[derive(Deserialize, Debug, Clone)]
+pub struct S3Config {
+    /// 如`http://127.0.0.1:9000`或`https://s3.us-east-1.amazonaws.com`
+    pub endpoint: String,
+    /// 存储桶名称
+    pub bucket: String,
+    /// 签名使用的区域
+    #[serde(default = "default_s3_region")]
+    pub region: String,
+    /// 访问密钥ID
+    pub access_key: String,
+    /// 访问密钥
+    pub secret_key: Secret,
+    /// 对象键模板，支持`{path}`和`{stem}`
+    #[serde(default = "default_s3_key_template")]
+    pub key_template: String,
+    /// 对象的Cache-Control
+    pub cache_control: Option<String>,
+    /// 请求超时（秒）
+    #[serde(default = "default_http_timeout_seconds", deserialize_with = "lenient")]
+    pub timeout_seconds: u64,
+}
+
+/// 以HTTP PUT/POST发送JSON
+#[derive(Deserialize, Debug, Clone)]
+pub struct WebhookConfig {
+    /// 支持`{path}`和`{stem}`占位符，可能包含令牌
+    pub url: Secret,
+    /// HTTP方法，默认为PUT
+    #[serde(default = "default_webhook_method")]
+    pub method: String,
+    /// 附加的请求头，如`Authorization`
+    #[serde(default)]
+    pub headers: HashMap<String, Secret>,
+    /// 只在内容变化时发送
+    #[serde(default, deserialize_with = "lenient")]
+    pub only_changed: bool,
+    /// 请求超时（秒）
+    #[serde(default = "default_http_timeout_seconds", deserialize_with = "lenient")]
+    pub timeout_seconds: u64,
+}
+
+/// MySQL缓存表，列为path、payload、content_hash、generated_at和row_count
+#[derive(Deserialize, Debug, Clone)]
+pub struct MysqlSinkConfig {
+    /// 表名
+    pub table: String,
+    /// 启动时执行`CREATE TABLE IF NOT EXISTS`
+    #[serde(default, deserialize_with = "lenient")]
+    pub create_table: bool,
+}
+
+fn default_s3_region() -> String {
+    "us-east-1".to_string()
+}
+
+fn default_s3_key_template() -> String {
+    "{path}".to_string()
+}
+
+fn default_webhook_method() -> String {
+    "PUT".to_string()
+}
+
+fn default_http_timeout_seconds() -> u64 {
+    30
+}
+
+/// 内置HTTP服务（serve模式）
+#[derive(Deserialize, Debug, Clone)]
+pub struct ServerConfig {
+    /// 监听地址
+    #[serde(default = "default_listen")]
+    pub listen: String,
+    /// 检查各SQL文件是否需要更新的间隔（秒）
+    #[serde(default = "default_refresh_seconds", deserialize_with = "lenient")]
+    pub refresh_seconds: u64,
+    /// 默认的Cache-Control，可在[files]中按文件覆盖
+    #[serde(default = "default_cache_control")]
+    pub cache_control: String,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            listen: default_listen(),
+            refresh_seconds: default_refresh_seconds(),
+            cache_control: default_cache_control(),
+        }
+    }
+}
+
+/// serve模式下的管理接口，所有请求需要`Authorization: Bearer <token>`
+#[derive(Deserialize, Debug, Clone)]
+pub struct AdminConfig {
+    /// 管理接口的令牌
+    pub token: Secret,
+    /// 每个SQL文件保留的执行记录数
+    #[serde(default = "default_history_size", deserialize_with = "lenient")]
+    pub history_size: usize,
+}
+
+/// 指标输出，serve模式下总是可以通过`/metrics`访问
+#[derive(Deserialize, Debug, Clone)]
+pub struct MetricsConfig {
+    /// 每轮运行后写入的node_exporter textfile，如`/var/lib/node_exporter/fastsql2json.prom`
+    pub textfile: Option<String>,
+}
+
+fn default_history_size() -> usize {
+    20
+}
+
+fn default_listen() -> String {
+    "127.0.0.1:8080".to_string()
+}
+
+fn default_refresh_seconds() -> u64 {
+    60
+}
+
+fn default_cache_control() -> String {
+    "no-cache".to_string()
+}
+
+/// 在每个JSON旁写入预压缩文件
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CompressionConfig {
+    /// 写入`.json.gz`
+    #[serde(default, deserialize_with = "lenient")]
+    pub gzip: bool,
+    /// 0-9
+    #[serde(default = "default_gzip_level", deserialize_with = "lenient")]
+    pub gzip_level: u32,
+    /// 写入`.json.br`
+    #[serde(default, deserialize_with = "lenient")]
+    pub brotli: bool,
+    /// 0-11
+    #[serde(default = "default_brotli_level", deserialize_with = "lenient")]
+    pub brotli_level: u32,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        CompressionConfig {
+            gzip: false,
+            gzip_level: default_gzip_level(),
+            brotli: false,
+            brotli_level: default_brotli_level(),
+        }
+    }
+}
+
+fn default_gzip_level() -> u32 {
+    9
+}
+
+fn default_brotli_level() -> u32 {
+    11
+}
+
+/// 单个SQL文件的配置，键与file_intervals相同（SQL文件路径）
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FileConfig {
+    /// 发布保护
+    pub guard: Option<GuardConfig>,
+    /// 该文件的JSON输出目录，覆盖`[app].output_dir`
+    pub output_dir: Option<String>,
+    /// 该文件的输出文件名模板，覆盖`[app].filename_template`
+    pub filename_template: Option<String>,
+    /// serve模式下该文件响应的Cache-Control
+    pub cache_control: Option<String>,
+    /// 该文件的输出列表，覆盖`[app].sinks`
+    pub sinks: Option<Vec<String>>,
+    /// 该文件使用的`[databases]`连接名称
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub database: Option<String>,
+    /// 该文件允许的最大复制延迟（秒），覆盖连接的`max_lag_seconds`
+    #[serde(skip_serializing_if = "Option::is_none", default, deserialize_with = "lenient_option")]
+    pub max_lag_seconds: Option<f64>,
+    /// 生成JSON时对每一行的转换
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transform: Option<TransformConfig>,
+}
+
+/// 逐行转换，依次执行计算列、默认值、类型转换、保留和删除列、重命名；
+/// 除`rename`的值以外，列名都是SQL结果中的列名（或计算列的名称）
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TransformConfig {
+    /// 计算列：列名 = 表达式，表达式引用SQL结果中的列
+    #[serde(default)]
+    pub computed: BTreeMap<String, String>,
+    /// 为NULL或不存在的列使用的值
+    #[serde(default)]
+    pub defaults: BTreeMap<String, serde_json::Value>,
+    /// 列值转换的目标类型
+    #[serde(default)]
+    pub cast: BTreeMap<String, CastType>,
+    /// 只保留这些列
+    pub keep: Option<Vec<String>>,
+    /// 删除这些列
+    #[serde(default)]
+    pub drop: Vec<String>,
+    /// 指定列的新名称，优先于`rename_case`
+    #[serde(default)]
+    pub rename: BTreeMap<String, String>,
+    /// 其余列名转换为的命名风格
+    pub rename_case: Option<RenameCase>,
+}
+
+/// 脱敏策略，生成JSON时在行转换之后执行，结果发送到任何输出之前已经脱敏
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct RedactionConfig {
+    /// `hash`和`tokenize`使用的HMAC密钥
+    pub salt: Option<Secret>,
+    /// 依次执行的规则
+    #[serde(default)]
+    pub rules: Vec<RedactionRule>,
+}
+
+/// 一条脱敏规则，`columns`和`pattern`至少设置一个；同时设置时只检查匹配列中的值
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RedactionRule {
+    /// 日志中的策略名称，默认为`rules[<序号>]`
+    pub name: Option<String>,
+    /// 列名的glob，不区分大小写，匹配行转换之后的列名，如`email`、`*_phone`
+    #[serde(default)]
+    pub columns: Vec<String>,
+    /// `email`、`phone`或正则表达式，只处理字符串和数字文本中匹配的部分，包括JSON列中的值
+    pub pattern: Option<String>,
+    /// 对匹配的值的处理方式
+    pub action: RedactionAction,
+}
+
+/// 脱敏的处理方式
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RedactionAction {
+    /// HMAC-SHA256的十六进制值
+    Hash,
+    /// 部分遮盖，如`j***@x.com`、`***1234`
+    Mask,
+    /// 删除整列
+    Drop,
+    /// 稳定的短令牌，如`tok_3f2a9c0d1b7e4a65`，同一个值总是得到同一个令牌
+    Tokenize,
+}
+
+/// 列值转换的目标类型，NULL保持不变
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CastType {
+    /// 布尔值
+    Bool,
+    /// 整数
+    Int,
+    /// 浮点数
+    Float,
+    /// 字符串
+    String,
+    /// 把字符串解析为JSON
+    Json,
+}
+
+/// 列名的命名风格
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum RenameCase {
+    /// `camelCase`
+    #[serde(rename = "camelCase")]
+    Camel,
+    /// `PascalCase`
+    #[serde(rename = "PascalCase")]
+    Pascal,
+    /// `snake_case`
+    #[serde(rename = "snake_case")]
+    Snake,
+    /// `kebab-case`
+    #[serde(rename = "kebab-case")]
+    Kebab,
+    /// `SCREAMING_SNAKE_CASE`
+    #[serde(rename = "SCREAMING_SNAKE_CASE")]
+    ScreamingSnake,
+}
+
+/// 发布保护：不满足条件时保留旧的JSON文件并视为失败
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct GuardConfig {
+    /// 最少行数
+    #[serde(default, deserialize_with = "lenient_option")]
+    pub min_rows: Option<usize>,
+    /// 与上一次生成的JSON相比，允许的最大行数降幅（百分比）
+    #[serde(default, deserialize_with = "lenient_option")]
+    pub max_drop_percent: Option<f64>,
+    /// 每一行都必须存在且不为NULL的列
+    #[serde(default)]
+    pub required_columns: Vec<String>,
 }
 
 impl Config {
+    /// 读取并解析配置文件
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
-        let content = fs::read_to_string(path)?;
-        let config: Config = from_str(&content)?;
+        Self::from_toml(&fs::read_to_string(path)?)
+    }
+    
+    /// 解析TOML格式的配置，替换环境变量并读取密码
+    pub fn from_toml(content: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut value: toml::Value = from_str(content)?;
+        interpolate_value(&mut value, &|name| std::env::var(name).ok())?;
+        let mut config: Config = value.try_into()?;
+        if let Some(database) = &mut config.database {
+            database.resolve_credentials("[database]")?;
+        }
+        for (name, database) in config.databases.iter_mut().flatten() {
+            if name == DEFAULT_DATABASE {
+                return Err(format!("[databases.{}] is reserved for [database]", name).into());
+            }
+            database.connection.resolve_credentials(&format!("[databases.{}]", name))?;
+            for pattern in &database.paths {
+                glob::Pattern::new(pattern).map_err(|e| format!("[databases.{}] paths '{}': {}", name, pattern, e))?;
+            }
+        }
+        if let Some(redaction) = &config.redaction {
+            crate::redaction::Redactor::new(redaction).map_err(|e| format!("[redaction] {}", e))?;
+        }
+        for (path, file_config) in config.files.iter().flatten() {
+            if let Some(transform) = &file_config.transform {
+                crate::transform::Transform::new(transform)
+                    .map_err(|e| format!("[files.\"{}\".transform] {}", path, e))?;
+            }
+        }
         Ok(config)
     }
     
+    /// SQL文件使用的连接名称，依次检查SQL文件中的`-- @database: <name>`指令、
+    /// `[files]`中的`database`和`[databases.<name>].paths`，都没有时使用默认连接
+    pub fn database_for(&self, relative_path: &str, directive: Option<&str>, file_config: &FileConfig) -> Result<String, Box<dyn std::error::Error>> {
+        if let Some(name) = directive.or(file_config.database.as_deref()) {
+            return Ok(name.to_string());
+        }
+        
+        let mut matched = Vec::new();
+        for (name, database) in self.databases.iter().flatten() {
+            for pattern in &database.paths {
+                if glob::Pattern::new(pattern)?.matches(relative_path) {
+                    matched.push(name.as_str());
+                    break;
+                }
+            }
+        }
+        matched.sort();
+        match matched.as_slice() {
+            [] => Ok(DEFAULT_DATABASE.to_string()),
+            [name] => Ok(name.to_string()),
+            names => Err(format!("{} matches paths of several databases: {}", relative_path, names.join(", ")).into()),
+        }
+    }
+    
+    /// SQL文件在`file_intervals`中的更新间隔（分钟）
     pub fn get_interval(&self, file_path: &str) -> Option<u64> {
         if let Some(intervals) = &self.file_intervals {
             intervals.get(file_path).cloned()
@@ -39,4 +702,251 @@ impl Config {
             None
         }
     }
+    
+    /// 未设置`state_file`时为`$XDG_CACHE_HOME/fastsql2json/state-<start_dir的哈希>.json`
+    /// （默认`~/.cache`），不同的start_dir使用不同的状态文件。不放在`output_dir`下，以免随输出目录被公开
+    pub fn state_file_path(&self) -> PathBuf {
+        if let Some(path) = &self.app.state_file {
+            return PathBuf::from(path);
+        }
+        let start_dir = fs::canonicalize(&self.app.start_dir).unwrap_or_else(|_| PathBuf::from(&self.app.start_dir));
+        let hash = FileHandler::content_hash(start_dir.to_string_lossy().as_bytes());
+        cache_dir().join("fastsql2json").join(format!("state-{}.json", &hash[..16]))
+    }
+    
+    /// 文件实际使用的输出名称列表
+    pub fn sink_names(&self, file_config: Option<&FileConfig>) -> Vec<String> {
+        if let Some(sinks) = file_config.and_then(|f| f.sinks.clone()) {
+            return sinks;
+        }
+        self.app.sinks.clone().unwrap_or_else(|| {
+            let mut sinks = vec!["file".to_string()];
+            if self.redis.is_some() {
+                sinks.push("redis".to_string());
+            }
+            sinks
+        })
+    }
+    
+    /// SQL文件在`[files]`中的配置，没有时为默认值
+    pub fn get_file_config(&self, file_path: &str) -> FileConfig {
+        self.files.as_ref()
+            .and_then(|files| files.get(file_path))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// 密码、令牌等敏感配置，`Debug`输出中显示为`***`
+#[derive(Deserialize, Clone, Default, PartialEq)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    /// 包装一个敏感值
+    pub fn new(value: impl Into<String>) -> Self {
+        Secret(value.into())
+    }
+    
+    /// 明文值，只在实际使用时调用
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+    
+    /// 是否为空字符串
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("\"***\"")
+    }
+}
+
+impl DatabaseConfig {
+    /// 端口，未设置时为所选数据库类型的默认端口
+    pub fn port(&self) -> u16 {
+        self.port.unwrap_or(match self.backend {
+            BackendKind::Postgres => 5432,
+            _ => 3306,
+        })
+    }
+    
+    /// 日志和运行报告中显示的服务器地址
+    pub fn address(&self) -> String {
+        match (&self.socket, self.backend) {
+            (_, BackendKind::Sqlite) => self.database.clone(),
+            (Some(socket), _) => socket.clone(),
+            (None, _) => format!("{}:{}", self.host, self.port()),
+        }
+    }
+    
+    /// 副本的连接选项，未设置的选项继承所属连接；副本设置了host时不再使用所属连接的socket
+    pub fn replica_config(&self, replica: &ReplicaConfig) -> DatabaseConfig {
+        let mut config = self.clone();
+        config.replicas.clear();
+        if let Some(host) = &replica.host {
+            config.host = host.clone();
+            config.socket = None;
+        }
+        if replica.port.is_some() {
+            config.port = replica.port;
+        }
+        if replica.socket.is_some() {
+            config.socket = replica.socket.clone();
+        }
+        if replica.user.is_some() {
+            config.user = replica.user.clone();
+        }
+        if replica.password.is_some() {
+            config.password = replica.password.clone();
+        }
+        config
+    }
+    
+    /// 从`password_file`和`option_file`补全未直接配置的用户名和密码，
+    /// `section`为错误信息中的配置段名称，如`[database]`、`[databases.reporting]`
+    pub fn resolve_credentials(&mut self, section: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if self.password.is_none() && let Some(path) = &self.password_file {
+            let content = fs::read_to_string(expand_home(path))
+                .map_err(|e| format!("{} failed to read password_file {}: {}", section, path, e))?;
+            self.password = Some(Secret::new(content.trim_end_matches(['\r', '\n'])));
+        }
+        
+        if let Some(path) = &self.option_file {
+            let options = read_option_file(&expand_home(path), self.login_path.as_deref())
+                .map_err(|e| format!("{} failed to read option_file {}: {}", section, path, e))?;
+            if self.user.is_none() {
+                self.user = options.get("user").cloned();
+            }
+            if self.password.is_none() {
+                self.password = options.get("password").map(Secret::new);
+            }
+        } else if self.login_path.is_some() {
+            return Err(format!("{} login_path requires option_file", section).into());
+        }
+        
+        if self.user.is_none() && self.backend != BackendKind::Sqlite {
+            return Err(format!("{} user is not set (user, option_file)", section).into());
+        }
+        Ok(())
+    }
+}
+
+/// `XDG_CACHE_HOME`，未设置时为`~/.cache`，没有`HOME`时为临时目录
+fn cache_dir() -> PathBuf {
+    match (std::env::var_os("XDG_CACHE_HOME"), std::env::var_os("HOME")) {
+        (Some(cache), _) if Path::new(&cache).is_absolute() => PathBuf::from(cache),
+        (_, Some(home)) => PathBuf::from(home).join(".cache"),
+        _ => std::env::temp_dir(),
+    }
+}
+
+/// 把`~/`开头的路径展开到`HOME`目录
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// 读取MySQL选项文件（`my.cnf`格式）中`[client]`段和`login_path`段的选项，后者优先
+///
+/// 不支持`mysql_config_editor`生成的加密`.mylogin.cnf`，请使用明文选项文件
+pub fn read_option_file(path: &Path, login_path: Option<&str>) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+    let bytes = fs::read(path)?;
+    let content = String::from_utf8(bytes)
+        .map_err(|_| "encrypted .mylogin.cnf is not supported, use a plain option file")?;
+    
+    let mut client = HashMap::new();
+    let mut login = HashMap::new();
+    if let Some(name) = login_path && !content.lines().any(|line| line.trim() == format!("[{}]", name)) {
+        return Err(format!("login path [{}] not found", name).into());
+    }
+    
+    let mut section = String::new();
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') || line.starts_with('!') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            section = name.trim().to_string();
+            continue;
+        }
+        let target = if section == "client" {
+            &mut client
+        } else if login_path == Some(section.as_str()) {
+            &mut login
+        } else {
+            continue;
+        };
+        let (key, value) = line.split_once('=').unwrap_or((line, ""));
+        let value = value.trim();
+        let value = value.strip_prefix('"').and_then(|v| v.strip_suffix('"'))
+            .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+            .unwrap_or(value);
+        target.insert(key.trim().replace('-', "_"), value.to_string());
+    }
+    
+    client.extend(login);
+    Ok(client)
+}
+
+/// 替换配置中所有字符串里的`${VAR}`和`${VAR:-默认值}`，`$${`表示字面的`${`
+///
+/// 替换后的值仍是字符串，数字和布尔类型的选项在解析配置时再转换；错误信息只包含配置项的路径，不包含值
+pub fn interpolate_value(value: &mut toml::Value, lookup: &dyn Fn(&str) -> Option<String>) -> Result<(), String> {
+    interpolate_at(value, "", lookup)
+}
+
+fn interpolate_at(value: &mut toml::Value, path: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Result<(), String> {
+    match value {
+        toml::Value::String(text) => *text = interpolate(text, path, lookup)?,
+        toml::Value::Array(items) => {
+            for (i, item) in items.iter_mut().enumerate() {
+                interpolate_at(item, &format!("{}[{}]", path, i), lookup)?;
+            }
+        }
+        toml::Value::Table(table) => {
+            for (key, item) in table.iter_mut() {
+                let path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                interpolate_at(item, &path, lookup)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn interpolate(text: &str, path: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start..];
+        if let Some(escaped) = after.strip_prefix("$${") {
+            result.push_str("${");
+            rest = escaped;
+        } else if let Some(reference) = after.strip_prefix("${") {
+            let end = reference.find('}').ok_or_else(|| format!("unterminated '${{' in {}", path))?;
+            let (name, default) = match reference[..end].split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (&reference[..end], None),
+            };
+            match (lookup(name), default) {
+                (Some(value), Some(default)) if value.is_empty() => result.push_str(default),
+                (Some(value), _) => result.push_str(&value),
+                (None, Some(default)) => result.push_str(default),
+                (None, None) => return Err(format!("environment variable {} is not set ({})", name, path)),
+            }
+            rest = &reference[end + 1..];
+        } else {
+            result.push('$');
+            rest = &after[1..];
+        }
+    }
+    result.push_str(rest);
+    Ok(result)
 }
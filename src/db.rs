@@ -1,41 +1,512 @@
-use crate::config::DatabaseConfig;
+use crate::backend::{self, Backend, ResultSet, SqlValue};
+use crate::config::{Config, DatabaseConfig, LagFallback, SslMode, DEFAULT_DATABASE};
 use mysql::*;
 use mysql::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::time::Duration;
 
+#[derive(Clone)]
 pub struct DbPool {
     pool: Pool,
+    /// 延迟连接且`ssl_mode = "preferred"`时不使用TLS的连接池，服务器不支持TLS时改用它
+    plain_pool: Option<Pool>,
+    tls_unsupported: Arc<AtomicBool>,
+    read_only: bool,
+    in_use: Arc<AtomicUsize>,
 }
 
 impl DbPool {
     pub fn new(config: &DatabaseConfig, _max_connections: usize, _timeout: Duration) -> Result<Self, Box<dyn std::error::Error>> {
-        let opts_builder = OptsBuilder::new()
-            .ip_or_hostname(Some(config.host.clone()))
-            .tcp_port(config.port)
-            .user(Some(config.user.clone()))
-            .pass(Some(config.password.clone()))
-            .db_name(Some(config.database.clone()))
-            .tcp_connect_timeout(Some(Duration::from_secs(10)))
-            .read_timeout(Some(Duration::from_secs(10)))
-            .write_timeout(Some(Duration::from_secs(10)));
-        
-        let pool = Pool::new(opts_builder)?;
+        let opts_builder = Self::opts(config);
+        let pool = match Pool::new(opts_builder.clone().ssl_opts(ssl_opts(config)?)) {
+            Err(Error::DriverError(DriverError::TlsNotSupported)) if config.ssl_mode == SslMode::Preferred => {
+                log::warn!("MySQL server does not support TLS, connecting without it (ssl_mode = \"preferred\")");
+                Pool::new(opts_builder)?
+            }
+            result => result?,
+        };
         
         Ok(DbPool {
             pool,
+            plain_pool: None,
+            tls_unsupported: Arc::new(AtomicBool::new(false)),
+            read_only: config.read_only,
+            in_use: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+    
+    /// 启动时不建立连接的连接池，用于只读副本，副本暂时不可用时不影响启动
+    ///
+    /// 第一次连接时才知道服务器是否支持TLS，`ssl_mode = "preferred"`时同时准备不使用TLS的连接池
+    pub fn new_lazy(config: &DatabaseConfig, max_connections: usize) -> Result<Self, Box<dyn std::error::Error>> {
+        let constraints = PoolConstraints::new(0, max_connections.max(1)).ok_or("invalid pool size")?;
+        let opts_builder = Self::opts(config)
+            .pool_opts(PoolOpts::default().with_constraints(constraints));
+        let plain_pool = if config.ssl_mode == SslMode::Preferred {
+            Some(Pool::new(opts_builder.clone())?)
+        } else {
+            None
+        };
+        Ok(DbPool {
+            pool: Pool::new(opts_builder.ssl_opts(ssl_opts(config)?))?,
+            plain_pool,
+            tls_unsupported: Arc::new(AtomicBool::new(false)),
+            read_only: config.read_only,
+            in_use: Arc::new(AtomicUsize::new(0)),
         })
     }
     
+    fn opts(config: &DatabaseConfig) -> OptsBuilder {
+        OptsBuilder::new()
+            .ip_or_hostname(Some(config.host.clone()))
+            .tcp_port(config.port())
+            .socket(config.socket.clone())
+            .user(config.user.clone())
+            .pass(config.password.as_ref().map(|password| password.expose().to_string()))
+            .db_name(Some(config.database.clone()))
+            .tcp_connect_timeout(Some(Duration::from_secs(10)))
+            .read_timeout(Some(Duration::from_secs(10)))
+            .write_timeout(Some(Duration::from_secs(10)))
+    }
+    
+    /// 当前从连接池取出、尚未归还的连接数
+    pub fn connections_in_use(&self) -> usize {
+        self.in_use.load(Ordering::Relaxed)
+    }
+    
+    fn get_conn(&self) -> Result<TrackedConn, Box<dyn std::error::Error>> {
+        let conn = match &self.plain_pool {
+            Some(plain_pool) if self.tls_unsupported.load(Ordering::Relaxed) => plain_pool.get_conn()?,
+            Some(plain_pool) => match self.pool.get_conn() {
+                Err(Error::DriverError(DriverError::TlsNotSupported)) => {
+                    log::warn!("MySQL server does not support TLS, connecting without it (ssl_mode = \"preferred\")");
+                    self.tls_unsupported.store(true, Ordering::Relaxed);
+                    plain_pool.get_conn()?
+                }
+                result => result?,
+            },
+            None => self.pool.get_conn()?,
+        };
+        self.in_use.fetch_add(1, Ordering::Relaxed);
+        Ok(TrackedConn { conn, in_use: self.in_use.clone() })
+    }
+    
+    /// 执行查询，开启read_only时在只读事务中执行，SQL文件无法修改数据
     pub fn execute_query(&self, query: &str) -> Result<Vec<Vec<Row>>, Box<dyn std::error::Error>> {
-        let mut conn = self.pool.get_conn()?;
-        let results = conn.query(query)?;
+        let mut conn = self.get_conn()?;
+        if !self.read_only {
+            let results = conn.query(query)?;
+            return Ok(vec![results]);
+        }
+        
+        let mut tx = conn.start_transaction(TxOpts::default().set_access_mode(Some(AccessMode::ReadOnly)))?;
+        let results = tx.query(query)?;
+        tx.commit()?;
         
         Ok(vec![results])
     }
     
+    /// 在单独的读写事务中执行写操作，闭包返回错误时回滚
+    ///
+    /// 使用连接池中另取的连接，不影响查询连接的只读事务
+    pub fn write_transaction<T, F>(&self, f: F) -> Result<T, Box<dyn std::error::Error>>
+    where
+        F: FnOnce(&mut Transaction) -> Result<T, Box<dyn std::error::Error>>,
+    {
+        let mut conn = self.get_conn()?;
+        let mut tx = conn.start_transaction(TxOpts::default().set_access_mode(Some(AccessMode::ReadWrite)))?;
+        let value = f(&mut tx)?;
+        tx.commit()?;
+        Ok(value)
+    }
+    
+    /// 通过`GET_LOCK()`获取命名锁，已被其他连接持有时返回None
+    ///
+    /// 锁绑定在一个独占的连接上，NamedLock释放时执行`RELEASE_LOCK()`
+    pub fn try_named_lock(&self, name: &str) -> Result<Option<NamedLock>, Box<dyn std::error::Error>> {
+        let mut conn = self.get_conn()?;
+        let acquired: Option<Option<i64>> = conn.exec_first("SELECT GET_LOCK(?, 0)", (name,))?;
+        if acquired.flatten() == Some(1) {
+            Ok(Some(NamedLock { conn, name: name.to_string() }))
+        } else {
+            Ok(None)
+        }
+    }
+    
+    /// 用反引号引用表名（可带库名），只允许字母、数字、下划线和`$`
+    pub fn quote_identifier(name: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let parts = name.split('.').collect::<Vec<_>>();
+        let valid = parts.len() <= 2 && parts.iter().all(|part| {
+            !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+        });
+        if !valid {
+            return Err(format!("Invalid table name '{}'", name).into());
+        }
+        Ok(parts.iter().map(|part| format!("`{}`", part)).collect::<Vec<_>>().join("."))
+    }
+    
     pub fn get_version(&self) -> Result<String, Box<dyn std::error::Error>> {
-        let mut conn = self.pool.get_conn()?;
+        let mut conn = self.get_conn()?;
         let version: String = conn.query_first("SELECT VERSION()")?.unwrap_or_else(|| "Unknown".to_string());
         Ok(version)
     }
+    
+    /// 副本的复制延迟（秒），复制未运行或未配置时返回None
+    ///
+    /// 设置`heartbeat_table`时按pt-heartbeat心跳表中最新的`ts`（UTC）计算，
+    /// 否则读取`SHOW REPLICA STATUS`的`Seconds_Behind_Source`（MySQL 8.0.22之前为`SHOW SLAVE STATUS`），
+    /// 多源复制取各通道中最大的延迟
+    pub fn replication_lag(&self, heartbeat_table: Option<&str>) -> Result<Option<f64>, Box<dyn std::error::Error>> {
+        let mut conn = self.get_conn()?;
+        if let Some(table) = heartbeat_table {
+            let query = format!(
+                "SELECT TIMESTAMPDIFF(MICROSECOND, MAX(ts), UTC_TIMESTAMP(6)) FROM {}",
+                Self::quote_identifier(table)?
+            );
+            let micros: Option<Option<i64>> = conn.query_first(query)?;
+            return Ok(micros.flatten().map(|micros| micros.max(0) as f64 / 1_000_000.0));
+        }
+        
+        let (rows, column): (Vec<Row>, _) = match conn.query("SHOW REPLICA STATUS") {
+            Ok(rows) => (rows, "Seconds_Behind_Source"),
+            Err(_) => (conn.query("SHOW SLAVE STATUS")?, "Seconds_Behind_Master"),
+        };
+        let lags: Option<Vec<u64>> = rows.iter()
+            .map(|row| row.get_opt::<Option<u64>, _>(column).and_then(|lag| lag.ok()).flatten())
+            .collect();
+        Ok(lags.and_then(|lags| lags.into_iter().max()).map(|lag| lag as f64))
+    }
+}
+
+impl Backend for DbPool {
+    fn query(&self, sql: &str) -> Result<Vec<ResultSet>, Box<dyn std::error::Error>> {
+        Ok(self.execute_query(sql)?.into_iter().map(result_set).collect())
+    }
+    
+    fn version(&self) -> Result<String, Box<dyn std::error::Error>> {
+        self.get_version()
+    }
+    
+    fn read_only(&self) -> bool {
+        self.read_only
+    }
+    
+    fn connections_in_use(&self) -> usize {
+        DbPool::connections_in_use(self)
+    }
+    
+    fn replication_lag(&self, heartbeat_table: Option<&str>) -> Result<Option<f64>, Box<dyn std::error::Error>> {
+        DbPool::replication_lag(self, heartbeat_table)
+    }
+    
+    fn as_mysql(&self) -> Option<&DbPool> {
+        Some(self)
+    }
+}
+
+fn result_set(rows: Vec<Row>) -> ResultSet {
+    let columns = rows.first().map(|row| row.columns()).unwrap_or_else(|| Arc::from([]));
+    mysql_result_set(&columns, rows.into_iter().map(Row::unwrap).collect())
+}
+
+/// 转换一个MySQL结果集，各列的值见[`mysql_value`]
+pub fn mysql_result_set(columns: &[Column], rows: Vec<Vec<Value>>) -> ResultSet {
+    ResultSet {
+        columns: columns.iter().map(|column| column.name_str().to_string()).collect(),
+        rows: rows.iter().map(|row| row.iter().map(mysql_value).collect()).collect(),
+    }
+}
+
+/// 文本协议返回的都是字符串，能解析为数值的按数值处理
+pub fn mysql_value(value: &Value) -> SqlValue {
+    match value {
+        Value::NULL => SqlValue::Null,
+        Value::Bytes(b) => {
+            let s = String::from_utf8(b.clone()).unwrap_or_default();
+            if let Ok(int_val) = s.parse::<i64>() {
+                SqlValue::Int(int_val)
+            } else if let Ok(float_val) = s.parse::<f64>()
+                && float_val.is_finite() {
+                SqlValue::Float(float_val)
+            } else {
+                SqlValue::Text(s)
+            }
+        },
+        Value::Int(i) => SqlValue::Int(*i),
+        Value::UInt(u) => SqlValue::UInt(*u),
+        Value::Float(f) => SqlValue::Float((*f).into()),
+        Value::Double(d) => SqlValue::Float(*d),
+        Value::Date(year, month, day, _, _, _, _) => {
+            SqlValue::Text(format!("{:04}-{:02}-{:02}", year, month, day))
+        },
+        Value::Time(_, _, hours, minutes, seconds, microseconds) => {
+            SqlValue::Text(format!("{:02}:{:02}:{:02}.{:06}", hours, minutes, seconds, microseconds))
+        },
+    }
+}
+
+/// 执行SQL文件的服务器，写入运行报告、执行记录和状态文件
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QueryTarget {
+    /// 连接名称，`default`为`[database]`
+    pub database: String,
+    /// `host:port`或socket路径
+    pub server: String,
+    /// 是否在副本上执行
+    pub replica: bool,
+    /// 执行前测得的复制延迟（秒），在主库上执行时为空
+    pub replication_lag_seconds: Option<f64>,
+}
+
+/// 没有延迟在允许范围内的副本，且`lag_fallback = "fail"`
+#[derive(Debug)]
+pub struct ReplicaLagError {
+    pub database: String,
+    pub max_lag_seconds: Option<f64>,
+    /// 各副本的地址和延迟，无法获取延迟时为None
+    pub lags: Vec<(String, Option<f64>)>,
+}
+
+impl fmt::Display for ReplicaLagError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lags: Vec<String> = self.lags.iter()
+            .map(|(server, lag)| match lag {
+                Some(lag) => format!("{} {}s", server, lag),
+                None => format!("{} unknown", server),
+            })
+            .collect();
+        match self.max_lag_seconds {
+            Some(max) => write!(f, "no replica of '{}' within {}s lag ({})", self.database, max, lags.join(", ")),
+            None => write!(f, "no replica of '{}' available ({})", self.database, lags.join(", ")),
+        }
+    }
+}
+
+impl std::error::Error for ReplicaLagError {}
+
+/// 延迟是否在允许范围内，未限制时任何可以连接的副本都可用，延迟未知时视为超出限制
+pub fn within_lag(lag: Option<f64>, max_lag_seconds: Option<f64>) -> bool {
+    match max_lag_seconds {
+        Some(max) => lag.is_some_and(|lag| lag <= max),
+        None => true,
+    }
+}
+
+/// 默认连接和`[databases.<name>]`命名连接各自的连接池
+#[derive(Clone)]
+pub struct DbPools {
+    default: Connection,
+    named: HashMap<String, Connection>,
+}
+
+/// 一个连接的主库和只读副本
+#[derive(Clone)]
+struct Connection {
+    primary: Arc<dyn Backend>,
+    address: String,
+    replicas: Vec<(String, Arc<dyn Backend>)>,
+    max_lag_seconds: Option<f64>,
+    heartbeat_table: Option<String>,
+    lag_fallback: LagFallback,
+}
+
+impl DbPools {
+    /// `default_backend`不为空时用它代替`[database]`，命名连接仍按配置建立；两者都没有时报错
+    pub fn new(config: &Config, default_backend: Option<Arc<dyn Backend>>) -> Result<Self, Box<dyn std::error::Error>> {
+        let default = match default_backend {
+            Some(primary) => Connection {
+                primary,
+                address: "embedded".to_string(),
+                replicas: Vec::new(),
+                max_lag_seconds: None,
+                heartbeat_table: None,
+                lag_fallback: LagFallback::default(),
+            },
+            None => {
+                let database = config.database.as_ref().ok_or("[database] is not set")?;
+                Self::connect(DEFAULT_DATABASE, database)?
+            }
+        };
+        let mut named = HashMap::new();
+        for (name, database) in config.databases.iter().flatten() {
+            named.insert(name.clone(), Self::connect(name, &database.connection)?);
+        }
+        Ok(DbPools { default, named })
+    }
+    
+    fn connect(name: &str, config: &DatabaseConfig) -> Result<Connection, Box<dyn std::error::Error>> {
+        let primary = backend::connect(config, false)
+            .map_err(|e| format!("failed to connect to database '{}': {}", name, e))?;
+        log::info!("Connected to {:?} database: {} ({})", config.backend, config.database, name);
+        
+        let mut replicas = Vec::new();
+        for replica in &config.replicas {
+            let replica = config.replica_config(replica);
+            let pool = backend::connect(&replica, true)
+                .map_err(|e| format!("invalid replica {} of database '{}': {}", replica.address(), name, e))?;
+            replicas.push((replica.address(), pool));
+        }
+        Ok(Connection {
+            primary,
+            address: config.address(),
+            replicas,
+            max_lag_seconds: config.max_lag_seconds,
+            heartbeat_table: config.heartbeat_table.clone(),
+            lag_fallback: config.lag_fallback,
+        })
+    }
+    
+    /// 由现成的连接组成的默认连接，测试中用来替换真实的数据库
+    #[cfg(test)]
+    pub fn with_backend(primary: Arc<dyn Backend>, replicas: Vec<(String, Arc<dyn Backend>)>) -> Self {
+        DbPools {
+            default: Connection {
+                primary,
+                address: "fake:3306".to_string(),
+                replicas,
+                max_lag_seconds: None,
+                heartbeat_table: None,
+                lag_fallback: LagFallback::default(),
+            },
+            named: HashMap::new(),
+        }
+    }
+    
+    /// `[database]`的连接
+    pub fn default_backend(&self) -> &dyn Backend {
+        self.default.primary.as_ref()
+    }
+    
+    /// `[database]`为MySQL时的连接池，命名锁和`mysql`输出需要
+    pub fn mysql_pool(&self) -> Option<&DbPool> {
+        self.default.primary.as_mysql()
+    }
+    
+    fn get(&self, name: &str) -> Result<&Connection, Box<dyn std::error::Error>> {
+        if name == DEFAULT_DATABASE {
+            return Ok(&self.default);
+        }
+        self.named.get(name).ok_or_else(|| format!("unknown database connection '{}'", name).into())
+    }
+    
+    /// 选择执行SQL文件的服务器，`default`为`[database]`
+    ///
+    /// 只读连接配置了副本时，依次使用第一个延迟不超过`max_lag_seconds`（文件的设置优先）的副本；
+    /// 都不满足时按`lag_fallback`在主库上执行或返回[`ReplicaLagError`]
+    pub fn route(&self, name: &str, max_lag_seconds: Option<f64>) -> Result<(&dyn Backend, QueryTarget), Box<dyn std::error::Error>> {
+        let connection = self.get(name)?;
+        let target = |server: &str, replica, replication_lag_seconds| QueryTarget {
+            database: name.to_string(),
+            server: server.to_string(),
+            replica,
+            replication_lag_seconds,
+        };
+        if connection.replicas.is_empty() || !connection.primary.read_only() {
+            return Ok((connection.primary.as_ref(), target(&connection.address, false, None)));
+        }
+        
+        let max_lag_seconds = max_lag_seconds.or(connection.max_lag_seconds);
+        let mut lags = Vec::new();
+        for (server, pool) in &connection.replicas {
+            let lag = match pool.replication_lag(connection.heartbeat_table.as_deref()) {
+                Ok(lag) => lag,
+                Err(e) => {
+                    log::warn!("Failed to check replication lag of {} ({}): {}", server, name, e);
+                    lags.push((server.clone(), None));
+                    continue;
+                }
+            };
+            if within_lag(lag, max_lag_seconds) {
+                return Ok((pool.as_ref(), target(server, true, lag)));
+            }
+            lags.push((server.clone(), lag));
+        }
+        
+        let error = ReplicaLagError { database: name.to_string(), max_lag_seconds, lags };
+        match connection.lag_fallback {
+            LagFallback::Primary => {
+                log::warn!("{}, using primary {}", error, connection.address);
+                Ok((connection.primary.as_ref(), target(&connection.address, false, None)))
+            }
+            LagFallback::Fail => Err(Box::new(error)),
+        }
+    }
+    
+    /// 所有连接池中使用中的连接数之和
+    pub fn connections_in_use(&self) -> usize {
+        std::iter::once(&self.default).chain(self.named.values())
+            .flat_map(|connection| std::iter::once(&connection.primary).chain(connection.replicas.iter().map(|(_, pool)| pool)))
+            .map(|pool| pool.connections_in_use())
+            .sum()
+    }
+}
+
+/// 连接池中的连接，归还时更新使用中的连接数
+struct TrackedConn {
+    conn: PooledConn,
+    in_use: Arc<AtomicUsize>,
+}
+
+impl Deref for TrackedConn {
+    type Target = PooledConn;
+    
+    fn deref(&self) -> &PooledConn {
+        &self.conn
+    }
+}
+
+impl DerefMut for TrackedConn {
+    fn deref_mut(&mut self) -> &mut PooledConn {
+        &mut self.conn
+    }
+}
+
+impl Drop for TrackedConn {
+    fn drop(&mut self) {
+        self.in_use.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+pub struct NamedLock {
+    conn: TrackedConn,
+    name: String,
+}
+
+impl Drop for NamedLock {
+    fn drop(&mut self) {
+        if let Err(e) = self.conn.exec_drop("SELECT RELEASE_LOCK(?)", (&self.name,)) {
+            log::error!("Failed to release lock {}: {}", self.name, e);
+        }
+    }
+}
+
+/// 按`ssl_mode`构造TLS选项，`disabled`时返回None；Unix socket连接不使用TLS
+pub fn ssl_opts(config: &DatabaseConfig) -> Result<Option<SslOpts>, Box<dyn std::error::Error>> {
+    if config.ssl_cert.is_some() != config.ssl_key.is_some() {
+        return Err("[database] ssl_cert and ssl_key must be set together".into());
+    }
+    if config.ssl_mode == SslMode::Disabled {
+        return Ok(None);
+    }
+    if !cfg!(feature = "tls") {
+        return Err("[database] ssl_mode requires building with the tls feature".into());
+    }
+    
+    let verify_ca = matches!(config.ssl_mode, SslMode::VerifyCa | SslMode::VerifyIdentity);
+    let opts = SslOpts::default()
+        .with_root_cert_path(config.ssl_ca.as_ref().map(PathBuf::from))
+        .with_danger_skip_domain_validation(config.ssl_mode != SslMode::VerifyIdentity)
+        .with_danger_accept_invalid_certs(!verify_ca || config.ssl_accept_invalid_certs);
+    #[cfg(feature = "tls")]
+    let opts = match (&config.ssl_cert, &config.ssl_key) {
+        (Some(cert), Some(key)) => opts.with_client_identity(Some(ClientIdentity::new(PathBuf::from(cert), PathBuf::from(key)))),
+        _ => opts,
+    };
+    Ok(Some(opts))
 }
@@ -0,0 +1,460 @@
+use serde_json::{Map, Number, Value};
+use std::cmp::Ordering;
+
+/// 计算列使用的简单表达式，如`price * quantity`、`first_name || ' ' || last_name`、
+/// `if(score >= 60, 'pass', 'fail')`
+///
+/// 与SQL相同，算术和`||`的操作数有NULL时结果为NULL，除以0得到NULL
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    Column(String),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Call(Function, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Concat,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+impl BinaryOp {
+    fn from_token(token: &str) -> Option<Self> {
+        Some(match token {
+            "+" => BinaryOp::Add,
+            "-" => BinaryOp::Sub,
+            "*" => BinaryOp::Mul,
+            "/" => BinaryOp::Div,
+            "%" => BinaryOp::Rem,
+            "||" => BinaryOp::Concat,
+            "=" | "==" => BinaryOp::Eq,
+            "!=" | "<>" => BinaryOp::Ne,
+            "<" => BinaryOp::Lt,
+            "<=" => BinaryOp::Le,
+            ">" => BinaryOp::Gt,
+            ">=" => BinaryOp::Ge,
+            "and" => BinaryOp::And,
+            "or" => BinaryOp::Or,
+            _ => return None,
+        })
+    }
+    
+    /// 优先级，数值越大结合越紧
+    fn binding_power(self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::And => 2,
+            BinaryOp::Eq | BinaryOp::Ne | BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => 4,
+            BinaryOp::Concat => 5,
+            BinaryOp::Add | BinaryOp::Sub => 6,
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => 7,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Function {
+    Lower,
+    Upper,
+    Trim,
+    Length,
+    Concat,
+    Coalesce,
+    Round,
+    Abs,
+    If,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "lower" => Function::Lower,
+            "upper" => Function::Upper,
+            "trim" => Function::Trim,
+            "length" => Function::Length,
+            "concat" => Function::Concat,
+            "coalesce" => Function::Coalesce,
+            "round" => Function::Round,
+            "abs" => Function::Abs,
+            "if" => Function::If,
+            _ => return None,
+        })
+    }
+    
+    /// 参数个数的范围
+    fn arity(self) -> (usize, usize) {
+        match self {
+            Function::Lower | Function::Upper | Function::Trim | Function::Length | Function::Abs => (1, 1),
+            Function::Concat | Function::Coalesce => (1, usize::MAX),
+            Function::Round => (1, 2),
+            Function::If => (3, 3),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(Number),
+    Str(String),
+    Ident(String),
+    /// 反引号中的列名，不会被当作关键字或函数
+    QuotedIdent(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Comma,
+}
+
+const OPERATORS: [&str; 16] = ["||", "==", "!=", "<>", "<=", ">=", "=", "<", ">", "+", "-", "*", "/", "%", "(", ")"];
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+        } else if c.is_ascii_digit() || (c == '.' && rest[1..].starts_with(|c: char| c.is_ascii_digit())) {
+            let end = rest.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(rest.len());
+            let number = &rest[..end];
+            let number = match number.parse::<i64>() {
+                Ok(int) => Number::from(int),
+                Err(_) => number.parse::<f64>().ok().and_then(Number::from_f64)
+                    .ok_or_else(|| format!("invalid number '{}'", number))?,
+            };
+            tokens.push(Token::Number(number));
+            rest = &rest[end..];
+        } else if c == '\'' || c == '"' {
+            // 字符串中两个连续的引号表示引号本身
+            let mut value = String::new();
+            let mut chars = rest[1..].char_indices();
+            let end = loop {
+                match chars.next() {
+                    Some((i, next)) if next == c => {
+                        if rest[1 + i + 1..].starts_with(c) {
+                            value.push(c);
+                            chars.next();
+                        } else {
+                            break 1 + i + 1;
+                        }
+                    }
+                    Some((_, next)) => value.push(next),
+                    None => return Err("unterminated string".to_string()),
+                }
+            };
+            tokens.push(Token::Str(value));
+            rest = &rest[end..];
+        } else if c == '`' {
+            // 包含空格等字符的列名写在反引号中
+            let end = rest[1..].find('`').ok_or("unterminated `column`")?;
+            tokens.push(Token::QuotedIdent(rest[1..1 + end].to_string()));
+            rest = &rest[end + 2..];
+        } else if c.is_alphabetic() || c == '_' {
+            let end = rest.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else if c == ',' {
+            tokens.push(Token::Comma);
+            rest = &rest[1..];
+        } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+            tokens.push(match *op {
+                "(" => Token::LParen,
+                ")" => Token::RParen,
+                op => Token::Op(op),
+            });
+            rest = &rest[op.len()..];
+        } else {
+            return Err(format!("unexpected character '{}'", c));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+    
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(format!("expected {:?}, found {:?}", expected, token)),
+            None => Err(format!("expected {:?} at end of expression", expected)),
+        }
+    }
+    
+    fn peek_binary(&self) -> Option<BinaryOp> {
+        match self.tokens.get(self.pos)? {
+            Token::Op(op) => BinaryOp::from_token(op),
+            Token::Ident(name) => BinaryOp::from_token(&name.to_lowercase()),
+            _ => None,
+        }
+    }
+    
+    fn expr(&mut self, min_power: u8) -> Result<Expr, String> {
+        let mut lhs = self.prefix()?;
+        while let Some(op) = self.peek_binary() {
+            let power = op.binding_power();
+            if power <= min_power {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.expr(power)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+    
+    fn prefix(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Number(number)) => Ok(Expr::Literal(Value::Number(number))),
+            Some(Token::Str(value)) => Ok(Expr::Literal(Value::String(value))),
+            Some(Token::QuotedIdent(name)) => Ok(Expr::Column(name)),
+            Some(Token::Op("-")) => Ok(Expr::Unary(UnaryOp::Neg, Box::new(self.expr(7)?))),
+            Some(Token::LParen) => {
+                let expr = self.expr(0)?;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            }
+            Some(Token::Ident(name)) => match name.to_lowercase().as_str() {
+                "null" => Ok(Expr::Literal(Value::Null)),
+                "true" => Ok(Expr::Literal(Value::Bool(true))),
+                "false" => Ok(Expr::Literal(Value::Bool(false))),
+                // not的优先级低于比较，`not a = b`即`not (a = b)`
+                "not" => Ok(Expr::Unary(UnaryOp::Not, Box::new(self.expr(3)?))),
+                lower if self.tokens.get(self.pos) == Some(&Token::LParen) => {
+                    let function = Function::from_name(lower).ok_or_else(|| format!("unknown function '{}'", name))?;
+                    self.pos += 1;
+                    let mut args = Vec::new();
+                    if self.tokens.get(self.pos) == Some(&Token::RParen) {
+                        self.pos += 1;
+                    } else {
+                        loop {
+                            args.push(self.expr(0)?);
+                            match self.next() {
+                                Some(Token::Comma) => continue,
+                                Some(Token::RParen) => break,
+                                _ => return Err(format!("expected ',' or ')' in arguments of {}", name)),
+                            }
+                        }
+                    }
+                    let (min, max) = function.arity();
+                    if args.len() < min || args.len() > max {
+                        return Err(format!("wrong number of arguments for {}", name));
+                    }
+                    Ok(Expr::Call(function, args))
+                }
+                _ => Ok(Expr::Column(name)),
+            },
+            Some(token) => Err(format!("unexpected {:?}", token)),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
+
+/// 解析表达式，列名区分大小写，关键字和函数名不区分
+pub fn parse(source: &str) -> Result<Expr, String> {
+    let mut parser = Parser { tokens: tokenize(source)?, pos: 0 };
+    let expr = parser.expr(0)?;
+    if let Some(token) = parser.tokens.get(parser.pos) {
+        return Err(format!("unexpected {:?}", token));
+    }
+    Ok(expr)
+}
+
+impl Expr {
+    /// 在一行上求值，行中不存在的列视为NULL
+    pub fn eval(&self, row: &Map<String, Value>) -> Result<Value, String> {
+        match self {
+            Expr::Literal(value) => Ok(value.clone()),
+            Expr::Column(name) => Ok(row.get(name).cloned().unwrap_or(Value::Null)),
+            Expr::Unary(UnaryOp::Neg, operand) => match operand.eval(row)? {
+                Value::Null => Ok(Value::Null),
+                value => arithmetic(BinaryOp::Sub, &Value::from(0), &value),
+            },
+            Expr::Unary(UnaryOp::Not, operand) => Ok(Value::Bool(!truthy(&operand.eval(row)?))),
+            Expr::Binary(BinaryOp::And, lhs, rhs) => {
+                Ok(Value::Bool(truthy(&lhs.eval(row)?) && truthy(&rhs.eval(row)?)))
+            }
+            Expr::Binary(BinaryOp::Or, lhs, rhs) => {
+                Ok(Value::Bool(truthy(&lhs.eval(row)?) || truthy(&rhs.eval(row)?)))
+            }
+            Expr::Binary(op, lhs, rhs) => binary(*op, &lhs.eval(row)?, &rhs.eval(row)?),
+            Expr::Call(function, args) => call(*function, args, row),
+        }
+    }
+}
+
+/// NULL、false、0、空字符串和空数组为假
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().is_some_and(|n| n != 0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(a) => !a.is_empty(),
+        Value::Object(o) => !o.is_empty(),
+    }
+}
+
+/// 拼接字符串时的文本形式
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn binary(op: BinaryOp, lhs: &Value, rhs: &Value) -> Result<Value, String> {
+    match op {
+        BinaryOp::Eq => Ok(Value::Bool(equals(lhs, rhs))),
+        BinaryOp::Ne => Ok(Value::Bool(!equals(lhs, rhs))),
+        BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+            if lhs.is_null() || rhs.is_null() {
+                return Ok(Value::Null);
+            }
+            let ordering = match (lhs, rhs) {
+                (Value::Number(a), Value::Number(b)) => a.as_f64().partial_cmp(&b.as_f64()),
+                (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+                _ => None,
+            }.ok_or_else(|| format!("cannot compare {} and {}", lhs, rhs))?;
+            Ok(Value::Bool(match op {
+                BinaryOp::Lt => ordering == Ordering::Less,
+                BinaryOp::Le => ordering != Ordering::Greater,
+                BinaryOp::Gt => ordering == Ordering::Greater,
+                _ => ordering != Ordering::Less,
+            }))
+        }
+        _ if lhs.is_null() || rhs.is_null() => Ok(Value::Null),
+        BinaryOp::Concat => Ok(Value::String(text(lhs) + &text(rhs))),
+        _ => arithmetic(op, lhs, rhs),
+    }
+}
+
+/// 整数和浮点数按数值比较，其他按JSON比较
+fn equals(lhs: &Value, rhs: &Value) -> bool {
+    match (lhs, rhs) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        _ => lhs == rhs,
+    }
+}
+
+/// 两个整数的加减乘和取余结果仍为整数，溢出时按浮点数计算；除法结果为浮点数
+fn arithmetic(op: BinaryOp, lhs: &Value, rhs: &Value) -> Result<Value, String> {
+    let (Value::Number(a), Value::Number(b)) = (lhs, rhs) else {
+        return Err(format!("cannot apply {:?} to {} and {}", op, lhs, rhs));
+    };
+    if let (Some(a), Some(b)) = (a.as_i64(), b.as_i64()) {
+        let result = match op {
+            BinaryOp::Add => a.checked_add(b),
+            BinaryOp::Sub => a.checked_sub(b),
+            BinaryOp::Mul => a.checked_mul(b),
+            BinaryOp::Rem if b == 0 => return Ok(Value::Null),
+            BinaryOp::Rem => a.checked_rem(b),
+            _ => None,
+        };
+        if let Some(result) = result {
+            return Ok(Value::from(result));
+        }
+    }
+    let (a, b) = (a.as_f64().unwrap_or(f64::NAN), b.as_f64().unwrap_or(f64::NAN));
+    let result = match op {
+        BinaryOp::Add => a + b,
+        BinaryOp::Sub => a - b,
+        BinaryOp::Mul => a * b,
+        BinaryOp::Div | BinaryOp::Rem if b == 0.0 => return Ok(Value::Null),
+        BinaryOp::Div => a / b,
+        _ => a % b,
+    };
+    Ok(Number::from_f64(result).map_or(Value::Null, Value::Number))
+}
+
+fn call(function: Function, args: &[Expr], row: &Map<String, Value>) -> Result<Value, String> {
+    match function {
+        Function::Coalesce => {
+            for arg in args {
+                let value = arg.eval(row)?;
+                if !value.is_null() {
+                    return Ok(value);
+                }
+            }
+            Ok(Value::Null)
+        }
+        Function::If => {
+            let branch = if truthy(&args[0].eval(row)?) { &args[1] } else { &args[2] };
+            branch.eval(row)
+        }
+        Function::Concat => {
+            let mut result = String::new();
+            for arg in args {
+                let value = arg.eval(row)?;
+                if !value.is_null() {
+                    result.push_str(&text(&value));
+                }
+            }
+            Ok(Value::String(result))
+        }
+        _ => {
+            let value = args[0].eval(row)?;
+            if value.is_null() {
+                return Ok(Value::Null);
+            }
+            match function {
+                Function::Lower => Ok(Value::String(text(&value).to_lowercase())),
+                Function::Upper => Ok(Value::String(text(&value).to_uppercase())),
+                Function::Trim => Ok(Value::String(text(&value).trim().to_string())),
+                Function::Length => Ok(Value::from(text(&value).chars().count())),
+                Function::Abs => match value.as_i64() {
+                    Some(int) if int != i64::MIN => Ok(Value::from(int.abs())),
+                    _ => number(&value).map(|n| Number::from_f64(n.abs()).map_or(Value::Null, Value::Number)),
+                },
+                _ => {
+                    let digits = match args.get(1) {
+                        Some(arg) => arg.eval(row)?.as_i64().ok_or("round() digits must be an integer")?,
+                        None => 0,
+                    };
+                    // f64的指数范围之外的位数都等价于边界值，先限制范围，避免取反时溢出
+                    let digits = digits.clamp(-308, 308);
+                    let n = number(&value)?;
+                    if digits <= 0 && n.abs() < i64::MAX as f64 {
+                        let scale = 10f64.powi((-digits) as i32);
+                        return Ok(Value::from(((n / scale).round() * scale) as i64));
+                    }
+                    let scale = 10f64.powi(digits as i32);
+                    let rounded = (n * scale).round() / scale;
+                    Ok(Number::from_f64(if rounded.is_finite() { rounded } else { n }).map_or(Value::Null, Value::Number))
+                }
+            }
+        }
+    }
+}
+
+fn number(value: &Value) -> Result<f64, String> {
+    value.as_f64().ok_or_else(|| format!("{} is not a number", value))
+}
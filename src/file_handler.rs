@@ -1,50 +1,149 @@
+use crate::config::{CompressionConfig, Config, FileConfig};
+use std::collections::HashMap;
 use std::fs::{self, File, OpenOptions};
 use std::io::{self, Write};
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
 use tempfile::NamedTempFile;
 use fs2::FileExt;
+use sha2::{Digest, Sha256};
+use flate2::write::GzEncoder;
+
+const DEFAULT_FILENAME_TEMPLATE: &str = "{stem}.json";
 
 pub struct FileHandler {
-    // 可以添加配置参数，如最大重试次数等
+    start_dir: PathBuf,
+    output_dir: Option<PathBuf>,
+    filename_template: Option<String>,
+    files: HashMap<String, FileConfig>,
+    compression: CompressionConfig,
 }
 
 impl FileHandler {
+    /// JSON写在SQL文件旁边
     pub fn new() -> Self {
-        FileHandler {}
+        FileHandler {
+            start_dir: PathBuf::from("."),
+            output_dir: None,
+            filename_template: None,
+            files: HashMap::new(),
+            compression: CompressionConfig::default(),
+        }
+    }
+    
+    /// 使用配置中的`output_dir`和`filename_template`（全局及单文件）
+    pub fn from_config(config: &Config) -> Self {
+        let mut handler = Self::new();
+        handler.start_dir = PathBuf::from(&config.app.start_dir);
+        handler.output_dir = config.app.output_dir.as_ref().map(PathBuf::from);
+        handler.filename_template = config.app.filename_template.clone();
+        handler.files = config.files.clone().unwrap_or_default();
+        handler.compression = config.compression.clone().unwrap_or_default();
+        handler
     }
     
     pub fn write_json_atomic<P: AsRef<Path>>(&self, file_path: P, content: &str) -> Result<(), Box<dyn std::error::Error>> {
+        write_file_atomic(file_path.as_ref(), content.as_bytes())
+    }
+    
+    /// 内容与现有文件相同时跳过写入（不改变mtime），否则原子写入并更新`.sha256`校验文件
+    /// 以及启用的`.gz`/`.br`预压缩文件
+    ///
+    /// 返回值表示是否实际写入了新内容
+    pub fn write_json_if_changed<P: AsRef<Path>>(&self, file_path: P, content: &str) -> Result<bool, Box<dyn std::error::Error>> {
         let file_path = file_path.as_ref();
+        let hash = Self::content_hash(content.as_bytes());
+        let sidecar = Self::sha256_path(file_path);
         
-        // 确保目录存在
-        if let Some(parent) = file_path.parent() {
-            fs::create_dir_all(parent)?;
+        if file_path.exists() && fs::read(file_path)? == content.as_bytes() {
+            if fs::read_to_string(&sidecar).map(|s| s.trim() == hash).unwrap_or(false) {
+                // 只更新校验文件的mtime，作为最近一次生成时间
+                OpenOptions::new().write(true).open(&sidecar)?.set_modified(SystemTime::now())?;
+            } else {
+                write_file_atomic(&sidecar, format!("{}\n", hash).as_bytes())?;
+            }
+            // 只补写新启用的压缩格式
+            self.write_compressed(file_path, content.as_bytes(), false)?;
+            return Ok(false);
         }
         
-        // 创建临时文件，与目标文件在同一目录下
-        let temp_file = NamedTempFile::new_in(file_path.parent().unwrap_or_else(|| Path::new(".")))?;
-        let temp_path = temp_file.path();
-        
-        // 写入内容到临时文件
-        let mut file = File::create(temp_path)?;
-        file.write_all(content.as_bytes())?;
-        file.sync_all()?;
-        
-        // 验证写入内容的完整性
-        let written_content = fs::read_to_string(temp_path)?;
-        if written_content != content {
-            return Err(Box::new(io::Error::new(
-                io::ErrorKind::Other,
-                "Failed to verify written content"
-            )));
+        self.write_compressed(file_path, content.as_bytes(), true)?;
+        self.write_json_atomic(file_path, content)?;
+        write_file_atomic(&sidecar, format!("{}\n", hash).as_bytes())?;
+        Ok(true)
+    }
+    
+    /// SHA-256十六进制摘要，同时用作强ETag
+    pub fn content_hash(content: &[u8]) -> String {
+        format!("{:x}", Sha256::digest(content))
+    }
+    
+    /// `kpi.json` -> `kpi.json.sha256`
+    pub fn sha256_path<P: AsRef<Path>>(file_path: P) -> PathBuf {
+        Self::sibling_path(file_path, "sha256")
+    }
+    
+    /// `kpi.json` -> `kpi.json.<ext>`
+    pub fn sibling_path<P: AsRef<Path>>(file_path: P, ext: &str) -> PathBuf {
+        let mut path = file_path.as_ref().as_os_str().to_owned();
+        path.push(".");
+        path.push(ext);
+        PathBuf::from(path)
+    }
+    
+    /// 写入`.json.gz`和`.json.br`预压缩文件，供Nginx `gzip_static`/`brotli_static`使用
+    ///
+    /// 未启用的格式会删除已有的文件，避免提供过期内容。`overwrite`为false时只写入缺少的文件
+    /// 以及与当前压缩级别的结果不同的文件，修改压缩级别后不必等到JSON内容变化
+    fn write_compressed(&self, file_path: &Path, content: &[u8], overwrite: bool) -> Result<(), Box<dyn std::error::Error>> {
+        let gz_path = Self::sibling_path(file_path, "gz");
+        if self.compression.gzip {
+            Self::write_sibling(&gz_path, &Self::gzip(content, self.compression.gzip_level)?, overwrite)?;
+        } else if gz_path.exists() {
+            fs::remove_file(&gz_path)?;
         }
         
-        // 原子性替换目标文件
-        fs::rename(temp_path, file_path)?;
+        let br_path = Self::sibling_path(file_path, "br");
+        if self.compression.brotli {
+            Self::write_sibling(&br_path, &Self::brotli(content, self.compression.brotli_level)?, overwrite)?;
+        } else if br_path.exists() {
+            fs::remove_file(&br_path)?;
+        }
         
         Ok(())
     }
     
+    fn write_sibling(path: &Path, compressed: &[u8], overwrite: bool) -> Result<(), Box<dyn std::error::Error>> {
+        if overwrite || fs::read(path).map(|existing| existing != compressed).unwrap_or(true) {
+            write_file_atomic(path, compressed)?;
+        }
+        Ok(())
+    }
+    
+    pub fn gzip(content: &[u8], level: u32) -> io::Result<Vec<u8>> {
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::new(level.min(9)));
+        encoder.write_all(content)?;
+        encoder.finish()
+    }
+    
+    pub fn brotli(content: &[u8], level: u32) -> io::Result<Vec<u8>> {
+        let mut output = Vec::new();
+        {
+            let mut encoder = brotli::CompressorWriter::new(&mut output, 4096, level.min(11), 22);
+            encoder.write_all(content)?;
+        }
+        Ok(output)
+    }
+    
+    /// 读取已存在的预压缩文件
+    pub fn read_compressed<P: AsRef<Path>>(file_path: P) -> Precompressed {
+        let file_path = file_path.as_ref();
+        Precompressed {
+            gzip: fs::read(Self::sibling_path(file_path, "gz")).ok(),
+            brotli: fs::read(Self::sibling_path(file_path, "br")).ok(),
+        }
+    }
+    
     pub fn get_last_modified_time<P: AsRef<Path>>(&self, file_path: P) -> Result<Option<u64>, Box<dyn std::error::Error>> {
         let file_path = file_path.as_ref();
         if !file_path.exists() {
@@ -61,14 +160,17 @@ impl FileHandler {
     
     pub fn should_update<P: AsRef<Path>>(&self, file_path: P, interval: Option<u64>) -> Result<bool, Box<dyn std::error::Error>> {
         let file_path = file_path.as_ref();
-        let json_path = self.sql_to_json_path(file_path);
+        let json_path = self.sql_to_json_path(file_path)?;
         
         if !json_path.exists() {
             return Ok(true);
         }
         
         if let Some(interval) = interval {
-            let last_modified = self.get_last_modified_time(&json_path)?
+            // 内容未变化时JSON文件不会重写，以校验文件的mtime为准
+            let sidecar = Self::sha256_path(&json_path);
+            let last_modified = self.get_last_modified_time(&sidecar)?
+                .or(self.get_last_modified_time(&json_path)?)
                 .unwrap_or(0);
             let now = std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)?
@@ -80,25 +182,149 @@ impl FileHandler {
         }
     }
     
-    pub fn sql_to_json_path<P: AsRef<Path>>(&self, sql_path: P) -> PathBuf {
+    /// 计算SQL文件对应的JSON输出路径
+    ///
+    /// 配置了`output_dir`时，按SQL文件相对于`start_dir`的目录结构映射到输出目录；
+    /// 文件名由模板生成，支持`{stem}`（kpi）和`{name}`（kpi.sql）占位符。
+    /// 结果路径不允许跳出输出根目录。
+    pub fn sql_to_json_path<P: AsRef<Path>>(&self, sql_path: P) -> Result<PathBuf, Box<dyn std::error::Error>> {
         let sql_path = sql_path.as_ref();
-        let mut json_path = sql_path.to_path_buf();
-        json_path.set_extension("json");
-        json_path
+        let file_config = self.files.get(sql_path.to_string_lossy().as_ref());
+        let output_dir = file_config.and_then(|f| f.output_dir.as_ref().map(PathBuf::from))
+            .or_else(|| self.output_dir.clone());
+        let template = file_config.and_then(|f| f.filename_template.as_deref())
+            .or(self.filename_template.as_deref())
+            .unwrap_or(DEFAULT_FILENAME_TEMPLATE);
+        
+        let stem = sql_path.file_stem().and_then(|s| s.to_str()).unwrap_or("result");
+        let name = sql_path.file_name().and_then(|s| s.to_str()).unwrap_or("result.sql");
+        let file_name = template.replace("{stem}", stem).replace("{name}", name);
+        let file_name = Path::new(&file_name);
+        if !Self::is_contained(file_name) {
+            return Err(Box::new(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Filename template '{}' escapes the output directory", template)
+            )));
+        }
+        
+        let json_path = match output_dir {
+            Some(output_dir) => {
+                let relative_dir = sql_path.parent()
+                    .and_then(|parent| parent.strip_prefix(&self.start_dir).ok())
+                    .filter(|dir| Self::is_contained(dir))
+                    .ok_or_else(|| io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("{} is not inside start_dir {}", sql_path.display(), self.start_dir.display())
+                    ))?;
+                output_dir.join(relative_dir).join(file_name)
+            },
+            None => sql_path.with_file_name(file_name),
+        };
+        
+        if json_path == sql_path {
+            return Err(Box::new(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Output path for {} would overwrite the SQL file", sql_path.display())
+            )));
+        }
+        
+        Ok(json_path)
     }
     
-    pub fn lock_file<P: AsRef<Path>>(&self, file_path: P) -> Result<File, Box<dyn std::error::Error>> {
+    /// 相对路径中只允许普通目录名，不允许`..`或绝对路径
+    fn is_contained(path: &Path) -> bool {
+        path.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    }
+    
+    /// 获取`<file>.lock`独占锁，其他进程持有时返回None
+    ///
+    /// 锁文件在FileLock释放时删除
+    pub fn lock_file<P: AsRef<Path>>(&self, file_path: P) -> Result<Option<FileLock>, Box<dyn std::error::Error>> {
         let file_path = file_path.as_ref();
-        let lock_path = format!("{}.lock", file_path.display());
+        let lock_path = PathBuf::from(format!("{}.lock", file_path.display()));
         
-        let file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .open(&lock_path)?;
-        
-        // 尝试获取独占锁
-        file.try_lock_exclusive()?;
+        for _ in 0..3 {
+            let file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&lock_path)?;
+            
+            // 尝试获取独占锁
+            if let Err(e) = file.try_lock_exclusive() {
+                if e.raw_os_error() == fs2::lock_contended_error().raw_os_error() {
+                    return Ok(None);
+                }
+                return Err(Box::new(e));
+            }
+            
+            // 上一个持有者可能在我们打开之后删除了锁文件，此时需要重新创建
+            if Self::is_same_file(&file, &lock_path) {
+                return Ok(Some(FileLock { file, path: lock_path }));
+            }
+        }
         
-        Ok(file)
+        Ok(None)
     }
+    
+    #[cfg(unix)]
+    fn is_same_file(file: &File, path: &Path) -> bool {
+        use std::os::unix::fs::MetadataExt;
+        match (file.metadata(), fs::metadata(path)) {
+            (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+            _ => false,
+        }
+    }
+    
+    #[cfg(not(unix))]
+    fn is_same_file(_file: &File, path: &Path) -> bool {
+        path.exists()
+    }
+}
+
+/// JSON的预压缩版本
+#[derive(Default)]
+pub struct Precompressed {
+    /// `.json.gz`的内容
+    pub gzip: Option<Vec<u8>>,
+    /// `.json.br`的内容
+    pub brotli: Option<Vec<u8>>,
+}
+
+/// 持有期间独占SQL文件，释放时删除锁文件
+pub struct FileLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        // 先删除再解锁，其他进程不会锁住一个即将被删除的文件
+        let _ = fs::remove_file(&self.path);
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+/// 通过同一目录下的临时文件原子写入，读取方不会看到写了一半的文件
+pub fn write_file_atomic(path: &Path, content: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    // 确保目录存在，没有父目录时写入当前目录
+    let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)?;
+    
+    // 创建临时文件，与目标文件在同一目录下
+    let mut temp_file = NamedTempFile::new_in(dir)?;
+    temp_file.write_all(content)?;
+    temp_file.as_file().sync_all()?;
+    
+    // 验证写入内容的完整性
+    let written_content = fs::read(temp_file.path())?;
+    if written_content != content {
+        return Err(Box::new(io::Error::other(
+            "Failed to verify written content"
+        )));
+    }
+    
+    // 原子性替换目标文件
+    temp_file.persist(path)?;
+    Ok(())
 }
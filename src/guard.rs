@@ -0,0 +1,66 @@
+use crate::config::GuardConfig;
+use serde_json::Value;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// 发布保护未通过，旧的JSON文件保持不变
+#[derive(Debug)]
+pub struct GuardViolation {
+    pub reason: String,
+}
+
+impl fmt::Display for GuardViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Publish guard violated: {}", self.reason)
+    }
+}
+
+impl std::error::Error for GuardViolation {}
+
+/// 在写入前检查新结果，`previous_rows`为上一次发布的行数，未知时不检查降幅
+pub fn check_publish(guard: &GuardConfig, root_name: &str, new_value: &Value, previous_rows: Option<usize>) -> Result<(), GuardViolation> {
+    let rows = new_value.get(root_name)
+        .and_then(|v| v.as_array())
+        .map(|a| a.as_slice())
+        .unwrap_or(&[]);
+    
+    if let Some(min_rows) = guard.min_rows
+        && rows.len() < min_rows {
+        return Err(GuardViolation {
+            reason: format!("{} rows, expected at least {}", rows.len(), min_rows),
+        });
+    }
+    
+    if let Some(max_drop) = guard.max_drop_percent
+        && let Some(previous) = previous_rows
+        && previous > 0
+        && rows.len() < previous {
+        let drop = (previous - rows.len()) as f64 * 100.0 / previous as f64;
+        if drop > max_drop {
+            return Err(GuardViolation {
+                reason: format!("row count dropped {:.1}% ({} -> {}), limit is {}%", drop, previous, rows.len(), max_drop),
+            });
+        }
+    }
+    
+    for column in &guard.required_columns {
+        let null_rows = rows.iter()
+            .filter(|row| row.get(column).is_none_or(Value::is_null))
+            .count();
+        if null_rows > 0 {
+            return Err(GuardViolation {
+                reason: format!("required column '{}' is missing or NULL in {} rows", column, null_rows),
+            });
+        }
+    }
+    
+    Ok(())
+}
+
+/// 读取已发布JSON中的行数，文件不存在或无法解析时返回None；状态文件中没有记录行数时使用
+pub fn previous_row_count(json_path: &Path, root_name: &str) -> Option<usize> {
+    let content = fs::read_to_string(json_path).ok()?;
+    let value: Value = serde_json::from_str(&content).ok()?;
+    value.get(root_name)?.as_array().map(|a| a.len())
+}
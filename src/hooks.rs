@@ -0,0 +1,67 @@
+use serde_json::Value;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::db::QueryTarget;
+
+/// 一个SQL文件的JSON发布到所有输出之后
+pub struct PublishEvent<'a> {
+    /// SQL文件的路径
+    pub sql_file: &'a Path,
+    /// SQL文件相对于start_dir的路径，如`subdir/kpi.sql`
+    pub relative_path: &'a str,
+    /// 内置HTTP服务中的路径，如`/subdir/kpi.json`
+    pub url_path: &'a str,
+    /// 发布的JSON
+    pub json: &'a Value,
+    /// 发布的JSON文本
+    pub content: &'a str,
+    /// 结果的行数，多个结果集时为各结果集行数之和
+    pub rows: usize,
+    /// 内容与上一次发布的不同
+    pub changed: bool,
+    /// 执行SQL的服务器
+    pub target: &'a QueryTarget,
+}
+
+/// 一个SQL文件处理失败，旧的JSON保持不变
+pub struct FailureEvent<'a> {
+    /// SQL文件的路径
+    pub sql_file: &'a Path,
+    /// SQL文件相对于start_dir的路径
+    pub relative_path: &'a str,
+    /// 与运行报告和指标中的`error_class`相同，如`sql`、`guard`、`sink`
+    pub error_class: &'static str,
+    /// 失败的原因
+    pub error: &'a (dyn std::error::Error + 'static),
+}
+
+/// 发布回调
+pub type PublishHook = Arc<dyn Fn(&PublishEvent) + Send + Sync>;
+/// 失败回调
+pub type FailureHook = Arc<dyn Fn(&FailureEvent) + Send + Sync>;
+
+/// 发布和失败时的回调，在处理SQL文件的阻塞线程中依次调用，应尽快返回
+#[derive(Default, Clone)]
+pub struct Hooks {
+    /// 按注册顺序调用的发布回调
+    pub on_publish: Vec<PublishHook>,
+    /// 按注册顺序调用的失败回调
+    pub on_failure: Vec<FailureHook>,
+}
+
+impl Hooks {
+    /// 调用所有发布回调
+    pub fn published(&self, event: &PublishEvent) {
+        for hook in &self.on_publish {
+            hook(event);
+        }
+    }
+    
+    /// 调用所有失败回调
+    pub fn failed(&self, event: &FailureEvent) {
+        for hook in &self.on_failure {
+            hook(event);
+        }
+    }
+}
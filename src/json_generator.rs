@@ -1,109 +1,86 @@
-use mysql::*;
+use base64::Engine;
 use serde_json::Value;
 use std::path::Path;
 
+use crate::backend::{ResultSet, SqlValue};
+use crate::redaction::{RedactionCounts, Redactor};
+use crate::transform::{Transform, TransformError};
+
 pub struct JsonGenerator {
-    _mysql_version: String,
+    _db_version: String,
 }
 
 impl JsonGenerator {
-    pub fn new(mysql_version: String) -> Self {
+    pub fn new(db_version: String) -> Self {
         JsonGenerator {
-            _mysql_version: mysql_version,
+            _db_version: db_version,
         }
     }
     
-    pub fn generate_json(&self, results: &[Vec<Row>], sql_file_path: &Path) -> Result<String, Box<dyn std::error::Error>> {
-        // 从SQL文件路径提取根节点名称（如kpi.sql -> kpi）
-        let root_name = sql_file_path.file_stem()
+    pub fn render(&self, value: &Value) -> Result<String, Box<dyn std::error::Error>> {
+        let json_str = serde_json::to_string_pretty(value)?;
+        Ok(json_str)
+    }
+    
+    /// 从SQL文件路径提取根节点名称（如kpi.sql -> kpi）
+    pub fn root_name(sql_file_path: &Path) -> &str {
+        sql_file_path.file_stem()
             .and_then(|stem| stem.to_str())
-            .unwrap_or("result");
+            .unwrap_or("result")
+    }
+    
+    pub fn generate_value(&self, results: &[ResultSet], sql_file_path: &Path, transform: &Transform, redactor: &Redactor) -> Result<Value, TransformError> {
+        let root_name = Self::root_name(sql_file_path);
+        let rows = Self::rows_value(results, transform, redactor, &sql_file_path.display().to_string())?;
         
         let mut output = serde_json::Map::new();
+        output.insert(root_name.to_string(), rows);
+        
+        Ok(Value::Object(output))
+    }
+    
+    /// 所有结果集的行按顺序合并为一个对象数组，每一行先经过`transform`转换，再由`redactor`脱敏，
+    /// 脱敏涉及的列按`label`记录到日志
+    pub fn rows_value(results: &[ResultSet], transform: &Transform, redactor: &Redactor, label: &str) -> Result<Value, TransformError> {
         let mut data_array = Vec::new();
+        let mut redacted = RedactionCounts::new();
         
-        for rows in results {
-            if rows.is_empty() {
-                continue;
-            }
-            
-            let columns = rows[0].columns_ref().iter()
-                .map(|col| col.name_str().to_string())
-                .collect::<Vec<String>>();
-            
-            for row in rows {
+        for result in results {
+            for row in &result.rows {
                 let mut obj = serde_json::Map::new();
-                for i in 0..columns.len() {
-                    let column_name = &columns[i];
-                    let json_value = if let Some(value) = row.as_ref(i) {
-                        self.convert_value(value)
-                    } else {
-                        Value::Null
-                    };
-                    obj.insert(column_name.clone(), json_value);
+                for (column_name, value) in result.columns.iter().zip(row) {
+                    obj.insert(column_name.clone(), Self::convert_value(value));
                 }
+                let mut obj = transform.apply(obj)?;
+                redactor.redact(&mut obj, &mut redacted);
                 data_array.push(Value::Object(obj));
             }
         }
+        Redactor::log_counts(label, &redacted);
         
-        output.insert(root_name.to_string(), Value::Array(data_array));
-        
-        let json_str = serde_json::to_string_pretty(&Value::Object(output))?;
-        Ok(json_str)
+        Ok(Value::Array(data_array))
     }
     
-    fn convert_value(&self, value: &mysql::Value) -> Value {
+    /// 非有限的浮点数输出为null，二进制数据输出为Base64字符串
+    pub fn convert_value(value: &SqlValue) -> Value {
         match value {
-            mysql::Value::NULL => Value::Null,
-            mysql::Value::Bytes(b) => {
-                let s = String::from_utf8(b.clone()).unwrap_or_default();
-                // 尝试将字符串转换为数值
-                if let Ok(int_val) = s.parse::<i64>() {
+            SqlValue::Null => Value::Null,
+            SqlValue::Bool(b) => Value::Bool(*b),
+            SqlValue::Int(i) => Value::Number((*i).into()),
+            SqlValue::UInt(u) => Value::Number((*u).into()),
+            SqlValue::Float(f) => serde_json::Number::from_f64(*f).map_or(Value::Null, Value::Number),
+            SqlValue::Decimal(d) => {
+                if let Ok(int_val) = d.parse::<i64>() {
                     Value::Number(int_val.into())
-                } else if let Ok(float_val) = s.parse::<f64>() {
-                    // 处理精度问题，去除不必要的尾部零
-                    let str_val = format!("{}", float_val);
-                    if str_val.contains('.') {
-                        let trimmed = str_val.trim_end_matches('0').trim_end_matches('.');
-                        if let Ok(fixed_float) = trimmed.parse::<f64>() {
-                            if let Some(num) = serde_json::Number::from_f64(fixed_float) {
-                                return Value::Number(num);
-                            }
-                        }
-                    }
-                    // 如果处理失败，直接使用原始转换
-                    if let Some(num) = serde_json::Number::from_f64(float_val) {
-                        Value::Number(num)
-                    } else {
-                        Value::String(s)
-                    }
-                } else {
-                    Value::String(s)
-                }
-            },
-            mysql::Value::Int(i) => Value::Number((*i).into()),
-            mysql::Value::UInt(u) => Value::Number((*u).into()),
-            mysql::Value::Float(f) => {
-                if let Some(num) = serde_json::Number::from_f64((*f).into()) {
-                    Value::Number(num)
-                } else {
-                    Value::Null
-                }
-            },
-            mysql::Value::Double(d) => {
-                if let Some(num) = serde_json::Number::from_f64(*d) {
+                } else if let Some(num) = d.parse::<f64>().ok().and_then(serde_json::Number::from_f64) {
                     Value::Number(num)
                 } else {
-                    Value::Null
+                    Value::String(d.clone())
                 }
             },
-            mysql::Value::Date(year, month, day, _, _, _, _) => {
-                Value::String(format!("{:04}-{:02}-{:02}", year, month, day))
-            },
-            mysql::Value::Time(_, _, hours, minutes, seconds, microseconds) => {
-                Value::String(format!("{:02}:{:02}:{:02}.{:06}", 
-                    hours, minutes, seconds, microseconds))
-            },
+            SqlValue::Text(s) => Value::String(s.clone()),
+            SqlValue::Bytes(b) => Value::String(base64::engine::general_purpose::STANDARD.encode(b)),
+            SqlValue::Json(v) => v.clone(),
         }
     }
 }
@@ -0,0 +1,87 @@
+//! FastSQL2Json：执行SQL文件并把结果发布为JSON
+//!
+//! 命令行程序只是这个库的一层包装，服务也可以直接嵌入同样的流程：
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use fastsql2json::{Config, Runner};
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let config = Config::from_file("config.toml")?;
+//! let runner = tokio::task::spawn_blocking(move || {
+//!     Runner::builder(config)
+//!         .on_publish(|event| log::info!("published {} ({} rows)", event.url_path, event.rows))
+//!         .on_failure(|event| log::warn!("{} failed: {}", event.relative_path, event.error))
+//!         .build()
+//!         .map_err(|e| e.to_string())
+//! }).await??;
+//! let runner = Arc::new(runner);
+//!
+//! // 处理start_dir下所有到期的SQL文件
+//! let report = runner.run_once().await;
+//! assert_eq!(report.failed, 0);
+//!
+//! // 强制处理单个文件，或者只执行SQL而不写入任何输出；这两个是阻塞调用，与build()一样
+//! // 放到spawn_blocking中（PostgreSQL客户端内部有自己的运行时，不能在异步任务中直接调用）
+//! let file = tokio::task::spawn_blocking({
+//!     let runner = runner.clone();
+//!     move || runner.run_file("sql_files/kpi.sql".as_ref(), true)
+//! }).await?;
+//! let rows = tokio::task::spawn_blocking({
+//!     let runner = runner.clone();
+//!     move || runner.render_query("SELECT 1 AS id").map_err(|e| e.to_string())
+//! }).await??;
+//! # Ok(())
+//! # }
+//! ```
+
+#![warn(missing_docs)]
+
+/// 配置文件
+pub mod config;
+/// 日志格式和按大小或周期轮转的日志文件
+pub mod logger;
+/// 与数据库无关的查询接口
+pub mod backend;
+mod db;
+#[cfg(feature = "postgres")]
+mod postgres_backend;
+#[cfg(feature = "sqlite")]
+mod sqlite_backend;
+mod scanner;
+mod sql_executor;
+mod json_generator;
+mod transform;
+mod redaction;
+mod expr;
+mod file_handler;
+mod guard;
+mod lock;
+mod state;
+/// 执行记录
+pub mod status;
+mod metrics;
+/// 运行报告和退出码
+pub mod report;
+/// 内置HTTP服务使用的内存中的JSON
+pub mod cache;
+mod query;
+mod redis_sink;
+mod s3_sink;
+mod sink;
+/// 发布和失败回调
+pub mod hooks;
+/// 扫描并处理SQL文件
+pub mod runner;
+/// 内置HTTP服务
+pub mod server;
+mod admin;
+mod tests;
+
+pub use backend::{Backend, ResultSet, SqlValue};
+pub use config::Config;
+pub use db::QueryTarget;
+pub use file_handler::Precompressed;
+pub use hooks::{FailureEvent, PublishEvent};
+pub use report::{FileReport, FileStatus, RunReport};
+pub use runner::{Runner, RunnerBuilder};
@@ -0,0 +1,38 @@
+use crate::config::{Config, LockBackend};
+use crate::db::NamedLock;
+use crate::file_handler::{FileHandler, FileLock};
+use crate::sql_executor::SqlExecutor;
+use std::path::Path;
+
+/// 处理单个SQL文件期间持有的锁，释放时自动解锁
+#[allow(dead_code)] // 只用于持有锁，由各自的Drop解锁
+pub enum RunLock {
+    File(FileLock),
+    Database(NamedLock),
+}
+
+/// 按配置的后端获取锁，已被其他运行实例持有时返回None
+pub fn acquire(
+    config: &Config,
+    sql_file: &Path,
+    relative_path: &str,
+    file_handler: &FileHandler,
+    sql_executor: &SqlExecutor
+) -> Result<Option<RunLock>, Box<dyn std::error::Error>> {
+    match config.app.lock_backend {
+        LockBackend::File => Ok(file_handler.lock_file(sql_file)?.map(RunLock::File)),
+        LockBackend::Mysql => {
+            let name = lock_name(relative_path);
+            let pool = sql_executor.mysql_pool().ok_or("lock_backend = \"mysql\" requires a MySQL [database]")?;
+            Ok(pool.try_named_lock(&name)?.map(RunLock::Database))
+        }
+    }
+}
+
+/// 由相对于start_dir的路径（与状态文件的键相同）生成锁名，各主机上的绝对路径可以不同
+///
+/// MySQL锁名最长64个字符，因此使用路径的哈希
+pub fn lock_name(relative_path: &str) -> String {
+    let hash = FileHandler::content_hash(relative_path.as_bytes());
+    format!("fastsql2json:{}", &hash[..40])
+}
@@ -1,27 +1,185 @@
-use log::LevelFilter;
-use std::io::Write;
-use std::time::SystemTime;
-
-pub fn init_logger(level: LevelFilter) -> Result<(), Box<dyn std::error::Error>> {
-    env_logger::builder()
-        .format(|buf, record| {
-            let timestamp = SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .unwrap()
-                .as_secs();
-            writeln!(buf, "[{}] [{}] - {}", timestamp, record.level(), record.args())
-        })
-        .filter(None, level)
-        .init();
+use chrono::{DateTime, SecondsFormat, Utc};
+use log::kv::{Key, Value, VisitSource};
+use log::{LevelFilter, Record};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::LazyLock;
+use regex::Regex;
+
+use crate::config::{LogConfig, LogFormat, Rotation};
+
+/// 初始化日志，`RUST_LOG`环境变量优先于`-q`/`-e`对应的级别
+pub fn init_logger(level: LevelFilter, config: Option<&LogConfig>) -> Result<(), Box<dyn std::error::Error>> {
+    let config = config.cloned().unwrap_or_default();
+    let format = config.format;
+    
+    let mut builder = env_logger::Builder::new();
+    builder.format(move |buf, record| writeln!(buf, "{}", format_record(format, record, Utc::now())));
+    match std::env::var("RUST_LOG") {
+        Ok(filters) if !filters.trim().is_empty() => builder.parse_filters(&filters),
+        _ => builder.filter_level(level),
+    };
+    
+    if let Some(file) = &config.file {
+        let writer = RotatingFile::open(
+            PathBuf::from(file),
+            config.max_size_mb.map(|mb| mb * 1024 * 1024),
+            config.rotation,
+            config.retention
+        )?;
+        builder.target(env_logger::Target::Pipe(Box::new(writer)));
+    }
+    
+    builder.try_init()?;
     Ok(())
 }
 
+/// 按格式输出一条日志，不含换行
+pub fn format_record(format: LogFormat, record: &Record, now: DateTime<Utc>) -> String {
+    let timestamp = now.to_rfc3339_opts(SecondsFormat::Millis, true);
+    match format {
+        LogFormat::Text => format!("[{}] [{}] - {}", timestamp, record.level(), record.args()),
+        LogFormat::Json => {
+            let mut fields = serde_json::Map::new();
+            fields.insert("timestamp".to_string(), timestamp.into());
+            fields.insert("level".to_string(), record.level().as_str().into());
+            fields.insert("target".to_string(), record.target().into());
+            fields.insert("message".to_string(), record.args().to_string().into());
+            let _ = record.key_values().visit(&mut JsonFields(&mut fields));
+            serde_json::Value::Object(fields).to_string()
+        }
+    }
+}
+
+/// 把日志的键值对（file、phase、duration_ms等）加入JSON对象
+struct JsonFields<'a>(&'a mut serde_json::Map<String, serde_json::Value>);
+
+impl<'kvs> VisitSource<'kvs> for JsonFields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), log::kv::Error> {
+        let value = if let Some(number) = value.to_u64() {
+            number.into()
+        } else if let Some(number) = value.to_i64() {
+            number.into()
+        } else if let Some(flag) = value.to_bool() {
+            flag.into()
+        } else if let Some(number) = value.to_f64() {
+            number.into()
+        } else {
+            value.to_string().into()
+        };
+        self.0.insert(key.as_str().to_string(), value);
+        Ok(())
+    }
+}
+
+/// 轮转文件名中`<文件名>.`之后的部分，如`20240101-120000.000000`或重名时的`20240101-120000.000000-1`
+static ROTATED_SUFFIX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\d{8}-\d{6}\.\d{6}(-\d+)?$").expect("valid rotated suffix pattern"));
+
+/// 按大小或时间轮转的日志文件
+///
+/// 轮转时把当前文件重命名为`<文件名>.<时间>`，只保留最近的`retention`个
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_bytes: Option<u64>,
+    rotation: Rotation,
+    period: Option<String>,
+    retention: usize,
+}
+
+impl RotatingFile {
+    /// 以追加方式打开日志文件，按需创建所在目录
+    pub fn open(path: PathBuf, max_bytes: Option<u64>, rotation: Rotation, retention: usize) -> io::Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let metadata = file.metadata()?;
+        // 已有的文件按修改时间归入对应的周期，跨周期后第一次写入时轮转
+        let modified: DateTime<Utc> = metadata.modified().map(Into::into).unwrap_or_else(|_| Utc::now());
+        Ok(RotatingFile {
+            period: Self::period_of(rotation, modified),
+            path,
+            file,
+            size: metadata.len(),
+            max_bytes,
+            rotation,
+            retention,
+        })
+    }
+    
+    fn period_of(rotation: Rotation, time: DateTime<Utc>) -> Option<String> {
+        match rotation {
+            Rotation::Never => None,
+            Rotation::Hourly => Some(time.format("%Y%m%d%H").to_string()),
+            Rotation::Daily => Some(time.format("%Y%m%d").to_string()),
+        }
+    }
+    
+    fn rotate(&mut self, now: DateTime<Utc>) -> io::Result<()> {
+        self.file.flush()?;
+        let file_name = self.path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        let stamp = now.format("%Y%m%d-%H%M%S%.6f");
+        let mut rotated = self.path.with_file_name(format!("{}.{}", file_name, stamp));
+        let mut counter = 1;
+        while rotated.exists() {
+            rotated = self.path.with_file_name(format!("{}.{}-{}", file_name, stamp, counter));
+            counter += 1;
+        }
+        fs::rename(&self.path, &rotated)?;
+        self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.size = 0;
+        self.prune(&file_name)
+    }
+    
+    /// 删除超出保留数量的轮转文件，文件名中的时间保证按名称排序即按时间排序；
+    /// 只处理本日志写入的轮转文件，同名前缀的其他文件（如`app.log.bak`）保持不变
+    fn prune(&self, file_name: &str) -> io::Result<()> {
+        let dir = self.path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(std::path::Path::new("."));
+        let prefix = format!("{}.", file_name);
+        let mut rotated: Vec<PathBuf> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().strip_prefix(&prefix)
+                .is_some_and(|suffix| ROTATED_SUFFIX.is_match(suffix)))
+            .map(|entry| entry.path())
+            .collect();
+        rotated.sort();
+        let excess = rotated.len().saturating_sub(self.retention);
+        for path in &rotated[..excess] {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let now = Utc::now();
+        let period = Self::period_of(self.rotation, now);
+        let too_large = self.max_bytes.is_some_and(|max| self.size + buf.len() as u64 > max);
+        if self.size > 0 && (too_large || period != self.period) {
+            self.rotate(now)?;
+        }
+        self.period = period;
+        self.file.write_all(buf)?;
+        self.size += buf.len() as u64;
+        Ok(buf.len())
+    }
+    
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// 记录单个SQL文件某个阶段的错误，JSON日志中带file和phase字段
 macro_rules! log_error {
     ($file_path:expr, $error:expr) => {
-        error!("Error in file {}: {}", $file_path, $error);
+        error!(file:% = $file_path; "Error in file {}: {}", $file_path, $error);
     };
     ($file_path:expr, $error_type:expr, $details:expr) => {
-        error!("Error in file {} - {}: {}", $file_path, $error_type, $details);
+        error!(file:% = $file_path, phase:% = $error_type; "Error in file {} - {}: {}", $file_path, $error_type, $details);
     };
 }
 
@@ -1,46 +1,67 @@
 use std::sync::Arc;
-use tokio::task::JoinSet;
-use std::time::Duration;
-use clap::Parser;
+use clap::error::ErrorKind;
+use clap::{Parser, Subcommand};
 use log::LevelFilter;
 
-mod config;
-mod logger;
-mod db;
-mod scanner;
-mod sql_executor;
-mod json_generator;
-mod file_handler;
-mod tests;
-
-use crate::config::Config;
-use crate::db::DbPool;
-use crate::scanner::scan_sql_files;
-use crate::sql_executor::SqlExecutor;
-use crate::json_generator::JsonGenerator;
-use crate::file_handler::FileHandler;
-use crate::logger::init_logger;
+use fastsql2json::cache::PayloadStore;
+use fastsql2json::logger::init_logger;
+use fastsql2json::{report, server, Config, Runner};
 
 /// FastSQL2Json - Convert SQL results to JSON files
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
     /// Path to the configuration file (default: config.toml in current directory)
-    #[arg(short, long, default_value = "config.toml")]
+    #[arg(short, long, default_value = "config.toml", global = true)]
     config: String,
     /// Disable all output
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     quiet: bool,
     /// Only output errors
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     error_only: bool,
+    /// Write a JSON run report to this path (overrides [app].report_file)
+    #[arg(long, global = true)]
+    report: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Keep refreshing SQL results and serve the generated JSON over HTTP
+    Serve {
+        /// Address to listen on (default: [server].listen or 127.0.0.1:8080)
+        #[arg(short, long)]
+        listen: Option<String>,
+    },
 }
 
+/// 退出码：0全部成功，1致命错误（包括命令行参数错误），2部分文件失败
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // 解析命令行参数
-    let args = Args::parse();
+async fn main() {
+    // 解析命令行参数；clap对参数错误使用退出码2，与部分文件失败冲突，改为致命错误
+    let args = match Args::try_parse() {
+        Ok(args) => args,
+        Err(e) if matches!(e.kind(), ErrorKind::DisplayHelp | ErrorKind::DisplayVersion) => e.exit(),
+        Err(e) => {
+            let _ = e.print();
+            std::process::exit(report::EXIT_FATAL);
+        }
+    };
     
+    let exit_code = match run(args).await {
+        Ok(exit_code) => exit_code,
+        Err(e) => {
+            log::error!("Fatal error: {}", e);
+            eprintln!("Error: {}", e);
+            report::EXIT_FATAL
+        }
+    };
+    std::process::exit(exit_code);
+}
+
+async fn run(args: Args) -> Result<i32, Box<dyn std::error::Error>> {
     // 设置日志级别
     let log_level = if args.quiet {
         LevelFilter::Off
@@ -50,112 +71,38 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         LevelFilter::Info
     };
     
-    // 初始化日志
-    init_logger(log_level)?;
+    // 读取配置文件，日志格式和日志文件在配置中设置
+    let mut config = Config::from_file(&args.config)?;
     
-    // 读取配置文件
-    let config = Config::from_file(&args.config)?;
+    // 初始化日志
+    init_logger(log_level, config.log.as_ref())?;
     log::info!("Loaded configuration from {}", args.config);
+    if let Some(report) = args.report {
+        config.app.report_file = Some(report);
+    }
     
-    // 创建数据库连接池
-    let db_pool = DbPool::new(
-        &config.database,
-        10, // 最大连接数
-        Duration::from_secs(30) // 超时时间
-    )?;
-    log::info!("Connected to MySQL database: {}", config.database.database);
-    
-    // 获取MySQL版本
-    let mysql_version = db_pool.get_version()?;
-    log::info!("MySQL version: {}", mysql_version);
-    
-    // 创建各个组件
-    let sql_executor = SqlExecutor::new(db_pool);
-    let json_generator = JsonGenerator::new(mysql_version);
-    let file_handler = FileHandler::new();
-    
-    // 扫描SQL文件
-    let sql_files = scan_sql_files(&config.app.start_dir);
-    log::info!("Found {} SQL files in directory: {}", sql_files.len(), config.app.start_dir);
-    
-    // 并发处理SQL文件
-    let sql_executor = Arc::new(sql_executor);
-    let json_generator = Arc::new(json_generator);
-    let file_handler = Arc::new(file_handler);
-    let config = Arc::new(config);
-    
-    let mut tasks = JoinSet::new();
-    let max_concurrent = 5;
-    
-    for sql_file in sql_files {
-        let sql_executor = sql_executor.clone();
-        let json_generator = json_generator.clone();
-        let file_handler = file_handler.clone();
-        let config = config.clone();
-        
-        tasks.spawn(async move {
-            if let Err(e) = process_sql_file(
-                &sql_file,
-                &sql_executor,
-                &json_generator,
-                &file_handler,
-                &config
-            ).await {
-                log::error!("Failed to process file {}: {}", sql_file.display(), e);
+    // 连接数据库是阻塞操作（PostgreSQL客户端内部有自己的运行时）
+    match args.command {
+        Some(Command::Serve { listen }) => {
+            let payload_store = Arc::new(PayloadStore::new());
+            let runner = tokio::task::block_in_place(|| {
+                Runner::builder(config).payload_store(payload_store.clone()).build()
+            })?;
+            server::serve(Arc::new(runner), payload_store, listen, args.config.into()).await?;
+            Ok(report::EXIT_OK)
+        },
+        None => {
+            let runner = tokio::task::block_in_place(|| Runner::new(config))?;
+            let report = Arc::new(runner).run_once().await;
+            if report.failed == 0 {
+                log::info!("All SQL files processed successfully");
+            } else {
+                log::error!("{} of {} SQL files failed", report.failed, report.files.len());
             }
-        });
-        
-        // 限制并发任务数
-        if tasks.len() >= max_concurrent {
-            if let Some(res) = tasks.join_next().await {
-                if let Err(e) = res {
-                    log::error!("Task failed: {}", e);
-                }
+            if !args.quiet {
+                print!("{}", report.summary_table());
             }
-        }
-    }
-    
-    // 等待所有任务完成
-    while let Some(res) = tasks.join_next().await {
-        if let Err(e) = res {
-            log::error!("Task failed: {}", e);
-        }
-    }
-    
-    log::info!("All SQL files processed successfully");
-    Ok(())
-}
-
-async fn process_sql_file(
-    sql_file: &std::path::Path,
-    sql_executor: &Arc<SqlExecutor>,
-    json_generator: &Arc<JsonGenerator>,
-    file_handler: &Arc<FileHandler>,
-    config: &Arc<Config>
-) -> Result<(), Box<dyn std::error::Error>> {
-    // 获取相对路径，用于配置文件中的间隔设置
-    let sql_file_str = sql_file.to_string_lossy().to_string();
-    let interval = config.get_interval(&sql_file_str);
-    
-    // 检查是否需要更新
-    if !file_handler.should_update(sql_file, interval)? {
-        log::debug!("Skipping file {} (not due for update)", sql_file.display());
-        return Ok(());
+            Ok(report.exit_code())
+        },
     }
-    
-    // 获取文件锁，防止并发处理
-    let _lock = file_handler.lock_file(sql_file)?;
-    
-    // 执行SQL文件
-    let results = sql_executor.execute_file(sql_file)?;
-    
-    // 生成JSON结果
-    let json_str = json_generator.generate_json(&results, sql_file)?;
-    
-    // 原子写入JSON文件
-    let json_path = file_handler.sql_to_json_path(sql_file);
-    file_handler.write_json_atomic(&json_path, &json_str)?;
-    
-    log::info!("Generated JSON file: {}", json_path.display());
-    Ok(())
 }
@@ -0,0 +1,172 @@
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::db::ReplicaLagError;
+use crate::guard::GuardViolation;
+use crate::sink::SinkError;
+use crate::transform::TransformError;
+
+/// 执行耗时直方图的桶上限（秒）
+const DURATION_BUCKETS: [f64; 11] = [0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+#[derive(Default)]
+struct FileMetrics {
+    duration_buckets: [u64; DURATION_BUCKETS.len()],
+    duration_count: u64,
+    duration_sum: f64,
+    rows: usize,
+    bytes_written: u64,
+    successes: u64,
+    failures: BTreeMap<&'static str, u64>,
+}
+
+/// Prometheus指标，按SQL文件相对路径区分
+pub struct Metrics {
+    files: Mutex<BTreeMap<String, FileMetrics>>,
+    /// 本轮尚未开始处理的文件数
+    pub queue_depth: AtomicUsize,
+    /// 正在处理的文件数
+    pub running: AtomicUsize,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics {
+            files: Mutex::new(BTreeMap::new()),
+            queue_depth: AtomicUsize::new(0),
+            running: AtomicUsize::new(0),
+        }
+    }
+    
+    /// 记录一次成功的生成，内容变化时计入写出的字节数
+    pub fn record_success(&self, path: &str, duration: Duration, rows: usize, bytes: usize, changed: bool) {
+        let mut files = self.files.lock().unwrap();
+        let file = files.entry(path.to_string()).or_default();
+        file.observe(duration);
+        file.rows = rows;
+        file.successes += 1;
+        if changed {
+            file.bytes_written += bytes as u64;
+        }
+    }
+    
+    pub fn record_failure(&self, path: &str, duration: Duration, error: &(dyn std::error::Error + 'static)) {
+        let mut files = self.files.lock().unwrap();
+        let file = files.entry(path.to_string()).or_default();
+        file.observe(duration);
+        *file.failures.entry(error_class(error)).or_default() += 1;
+    }
+    
+    /// 按Prometheus文本格式输出，`last_success`为各文件上一次成功生成的时间
+    pub fn render(&self, last_success: &[(String, DateTime<Utc>)], connections_in_use: usize, now: DateTime<Utc>) -> String {
+        let files = self.files.lock().unwrap();
+        let mut out = String::new();
+        
+        header(&mut out, "fastsql2json_query_duration_seconds", "histogram", "SQL file processing duration");
+        for (path, file) in files.iter() {
+            let label = escape(path);
+            for (bound, count) in DURATION_BUCKETS.iter().zip(file.duration_buckets) {
+                let _ = writeln!(out, "fastsql2json_query_duration_seconds_bucket{{file=\"{}\",le=\"{}\"}} {}", label, bound, count);
+            }
+            let _ = writeln!(out, "fastsql2json_query_duration_seconds_bucket{{file=\"{}\",le=\"+Inf\"}} {}", label, file.duration_count);
+            let _ = writeln!(out, "fastsql2json_query_duration_seconds_sum{{file=\"{}\"}} {}", label, file.duration_sum);
+            let _ = writeln!(out, "fastsql2json_query_duration_seconds_count{{file=\"{}\"}} {}", label, file.duration_count);
+        }
+        
+        header(&mut out, "fastsql2json_rows", "gauge", "Rows in the last generated result");
+        for (path, file) in files.iter().filter(|(_, file)| file.successes > 0) {
+            let _ = writeln!(out, "fastsql2json_rows{{file=\"{}\"}} {}", escape(path), file.rows);
+        }
+        
+        header(&mut out, "fastsql2json_bytes_written_total", "counter", "Bytes of changed JSON published");
+        for (path, file) in files.iter() {
+            let _ = writeln!(out, "fastsql2json_bytes_written_total{{file=\"{}\"}} {}", escape(path), file.bytes_written);
+        }
+        
+        header(&mut out, "fastsql2json_runs_succeeded_total", "counter", "Successful generations");
+        for (path, file) in files.iter() {
+            let _ = writeln!(out, "fastsql2json_runs_succeeded_total{{file=\"{}\"}} {}", escape(path), file.successes);
+        }
+        
+        header(&mut out, "fastsql2json_runs_failed_total", "counter", "Failed generations by error class");
+        for (path, file) in files.iter() {
+            for (class, count) in &file.failures {
+                let _ = writeln!(out, "fastsql2json_runs_failed_total{{file=\"{}\",class=\"{}\"}} {}", escape(path), class, count);
+            }
+        }
+        
+        header(&mut out, "fastsql2json_last_success_age_seconds", "gauge", "Seconds since the last successful generation");
+        for (path, generated_at) in last_success {
+            let age = (now - *generated_at).num_milliseconds().max(0) as f64 / 1000.0;
+            let _ = writeln!(out, "fastsql2json_last_success_age_seconds{{file=\"{}\"}} {}", escape(path), age);
+        }
+        
+        header(&mut out, "fastsql2json_db_connections_in_use", "gauge", "Database connections checked out of the pool");
+        let _ = writeln!(out, "fastsql2json_db_connections_in_use {}", connections_in_use);
+        
+        header(&mut out, "fastsql2json_scheduler_queue_depth", "gauge", "SQL files waiting to be processed in the current run");
+        let _ = writeln!(out, "fastsql2json_scheduler_queue_depth {}", self.queue_depth.load(Ordering::Relaxed));
+        
+        header(&mut out, "fastsql2json_scheduler_running", "gauge", "SQL files being processed");
+        let _ = writeln!(out, "fastsql2json_scheduler_running {}", self.running.load(Ordering::Relaxed));
+        
+        out
+    }
+}
+
+impl FileMetrics {
+    fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        for (bound, count) in DURATION_BUCKETS.iter().zip(self.duration_buckets.iter_mut()) {
+            if seconds <= *bound {
+                *count += 1;
+            }
+        }
+        self.duration_count += 1;
+        self.duration_sum += seconds;
+    }
+}
+
+/// 错误分类：guard、sink、lag、transform、sql、io或other；各数据库后端的错误都是sql
+pub fn error_class(error: &(dyn std::error::Error + 'static)) -> &'static str {
+    if error.is::<GuardViolation>() {
+        "guard"
+    } else if error.is::<SinkError>() {
+        "sink"
+    } else if error.is::<ReplicaLagError>() {
+        "lag"
+    } else if error.is::<TransformError>() {
+        "transform"
+    } else if is_sql_error(error) {
+        "sql"
+    } else if error.is::<std::io::Error>() {
+        "io"
+    } else {
+        "other"
+    }
+}
+
+fn is_sql_error(error: &(dyn std::error::Error + 'static)) -> bool {
+    #[cfg(feature = "postgres")]
+    if error.is::<postgres::Error>() {
+        return true;
+    }
+    #[cfg(feature = "sqlite")]
+    if error.is::<rusqlite::Error>() {
+        return true;
+    }
+    error.is::<mysql::Error>()
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
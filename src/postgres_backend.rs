@@ -0,0 +1,230 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat, Utc};
+use postgres::types::{FromSql, Kind, Type};
+use postgres::{Client, NoTls, Row};
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::backend::{Backend, ResultSet, SqlValue};
+use crate::config::{DatabaseConfig, SslMode};
+use crate::db::DbPool;
+
+/// 连接池中最多保留的空闲连接数
+const MAX_IDLE_CONNECTIONS: usize = 10;
+
+/// PostgreSQL连接池，连接在第一次使用时建立，用完后放回池中
+///
+/// SQL文件只能包含一条语句；`read_only`时在`READ ONLY`事务中执行
+pub struct PostgresBackend {
+    config: postgres::Config,
+    idle: Mutex<Vec<Client>>,
+    in_use: AtomicUsize,
+    read_only: bool,
+}
+
+impl PostgresBackend {
+    pub fn new(config: &DatabaseConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        if !matches!(config.ssl_mode, SslMode::Disabled | SslMode::Preferred) {
+            return Err("[database] TLS is not supported by the postgres backend, use ssl_mode = \"disabled\"".into());
+        }
+        
+        let mut pg_config = postgres::Config::new();
+        // 以`/`开头的host是Unix socket所在的目录
+        pg_config
+            .host(config.socket.as_deref().unwrap_or(&config.host))
+            .port(config.port())
+            .dbname(&config.database)
+            .application_name("fastsql2json")
+            .connect_timeout(Duration::from_secs(10));
+        if let Some(user) = &config.user {
+            pg_config.user(user);
+        }
+        if let Some(password) = &config.password {
+            pg_config.password(password.expose());
+        }
+        
+        Ok(PostgresBackend {
+            config: pg_config,
+            idle: Mutex::new(Vec::new()),
+            in_use: AtomicUsize::new(0),
+            read_only: config.read_only,
+        })
+    }
+    
+    fn with_client<T, F>(&self, f: F) -> Result<T, Box<dyn std::error::Error>>
+    where
+        F: FnOnce(&mut Client) -> Result<T, Box<dyn std::error::Error>>,
+    {
+        let idle = self.idle.lock().unwrap().pop();
+        let mut client = match idle {
+            Some(client) if !client.is_closed() => client,
+            _ => self.config.connect(NoTls)?,
+        };
+        
+        self.in_use.fetch_add(1, Ordering::Relaxed);
+        let result = f(&mut client);
+        self.in_use.fetch_sub(1, Ordering::Relaxed);
+        
+        if !client.is_closed() {
+            let mut idle = self.idle.lock().unwrap();
+            if idle.len() < MAX_IDLE_CONNECTIONS {
+                idle.push(client);
+            }
+        }
+        result
+    }
+}
+
+impl Backend for PostgresBackend {
+    fn query(&self, sql: &str) -> Result<Vec<ResultSet>, Box<dyn std::error::Error>> {
+        self.with_client(|client| {
+            let mut tx = client.build_transaction().read_only(self.read_only).start()?;
+            let statement = tx.prepare(sql)?;
+            let rows = tx.query(&statement, &[])?;
+            tx.commit()?;
+            
+            let columns = statement.columns();
+            let mut result = ResultSet {
+                columns: columns.iter().map(|column| column.name().to_string()).collect(),
+                rows: Vec::with_capacity(rows.len()),
+            };
+            for row in &rows {
+                let values = columns.iter().enumerate()
+                    .map(|(i, column)| pg_value(row, i, column.type_())
+                        .map_err(|e| format!("column {}: {}", column.name(), e)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                result.rows.push(values);
+            }
+            Ok(vec![result])
+        })
+    }
+    
+    fn version(&self) -> Result<String, Box<dyn std::error::Error>> {
+        self.with_client(|client| {
+            let version: String = client.query_one("SHOW server_version", &[])?.try_get(0)?;
+            Ok(format!("PostgreSQL {}", version))
+        })
+    }
+    
+    fn read_only(&self) -> bool {
+        self.read_only
+    }
+    
+    fn connections_in_use(&self) -> usize {
+        self.in_use.load(Ordering::Relaxed)
+    }
+    
+    /// 设置`heartbeat_table`时按心跳表中最新的`ts`（timestamptz）计算，
+    /// 否则按备库最后回放的事务时间`pg_last_xact_replay_timestamp()`计算
+    fn replication_lag(&self, heartbeat_table: Option<&str>) -> Result<Option<f64>, Box<dyn std::error::Error>> {
+        let query = match heartbeat_table {
+            Some(table) => format!(
+                "SELECT EXTRACT(EPOCH FROM now() - MAX(ts))::float8 FROM {}",
+                DbPool::quote_identifier(table)?.replace('`', "\"")
+            ),
+            None => "SELECT CASE WHEN pg_is_in_recovery() \
+                     THEN EXTRACT(EPOCH FROM now() - pg_last_xact_replay_timestamp())::float8 END".to_string(),
+        };
+        self.with_client(|client| {
+            let lag: Option<f64> = client.query_one(query.as_str(), &[])?.try_get(0)?;
+            Ok(lag.map(|lag| lag.max(0.0)))
+        })
+    }
+}
+
+/// 按列类型转换，日期和时间转为ISO格式的字符串，不支持的类型需要在SQL中转换为text
+fn pg_value(row: &Row, i: usize, ty: &Type) -> Result<SqlValue, Box<dyn std::error::Error>> {
+    let value = match ty.name() {
+        "bool" => row.try_get::<_, Option<bool>>(i)?.map(SqlValue::Bool),
+        "int2" => row.try_get::<_, Option<i16>>(i)?.map(|v| SqlValue::Int(v.into())),
+        "int4" => row.try_get::<_, Option<i32>>(i)?.map(|v| SqlValue::Int(v.into())),
+        "int8" => row.try_get::<_, Option<i64>>(i)?.map(SqlValue::Int),
+        "oid" => row.try_get::<_, Option<u32>>(i)?.map(|v| SqlValue::UInt(v.into())),
+        "float4" => row.try_get::<_, Option<f32>>(i)?.map(|v| SqlValue::Float(v.into())),
+        "float8" => row.try_get::<_, Option<f64>>(i)?.map(SqlValue::Float),
+        "numeric" => row.try_get::<_, Option<PgNumeric>>(i)?.map(|v| SqlValue::Decimal(v.0)),
+        "json" | "jsonb" => row.try_get::<_, Option<serde_json::Value>>(i)?.map(SqlValue::Json),
+        "date" => row.try_get::<_, Option<NaiveDate>>(i)?.map(|v| SqlValue::Text(v.to_string())),
+        "time" => row.try_get::<_, Option<NaiveTime>>(i)?
+            .map(|v| SqlValue::Text(v.format("%H:%M:%S%.f").to_string())),
+        "timestamp" => row.try_get::<_, Option<NaiveDateTime>>(i)?
+            .map(|v| SqlValue::Text(v.format("%Y-%m-%d %H:%M:%S%.f").to_string())),
+        "timestamptz" => row.try_get::<_, Option<DateTime<Utc>>>(i)?
+            .map(|v| SqlValue::Text(v.to_rfc3339_opts(SecondsFormat::AutoSi, true))),
+        "bytea" => row.try_get::<_, Option<Vec<u8>>>(i)?.map(SqlValue::Bytes),
+        _ if PgText::accepts(ty) => row.try_get::<_, Option<PgText>>(i)?.map(|v| SqlValue::Text(v.0)),
+        name => return Err(format!("unsupported PostgreSQL type {}, cast it to text", name).into()),
+    };
+    Ok(value.unwrap_or(SqlValue::Null))
+}
+
+/// 文本类型和枚举
+struct PgText(String);
+
+impl<'a> FromSql<'a> for PgText {
+    fn from_sql(_: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        Ok(PgText(std::str::from_utf8(raw)?.to_string()))
+    }
+    
+    fn accepts(ty: &Type) -> bool {
+        <String as FromSql>::accepts(ty) || matches!(ty.kind(), Kind::Enum(_))
+    }
+}
+
+/// NUMERIC的十进制字符串
+struct PgNumeric(String);
+
+impl<'a> FromSql<'a> for PgNumeric {
+    fn from_sql(_: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        Ok(PgNumeric(decode_numeric(raw)?))
+    }
+    
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::NUMERIC
+    }
+}
+
+/// 解析NUMERIC的二进制格式：位数、权重、符号、小数位数和以10000为基数的各位
+pub fn decode_numeric(raw: &[u8]) -> Result<String, String> {
+    let read = |offset: usize| raw.get(offset..offset + 2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| "invalid numeric value".to_string());
+    let ndigits = read(0)? as usize;
+    let weight = read(2)? as i16 as i32;
+    let sign = read(4)?;
+    let dscale = read(6)? as usize;
+    let digits = (0..ndigits).map(|i| read(8 + i * 2)).collect::<Result<Vec<_>, _>>()?;
+    let digit = |index: i32| if index < 0 { 0 } else { digits.get(index as usize).copied().unwrap_or(0) };
+    
+    match sign {
+        0x0000 | 0x4000 => {}
+        0xC000 => return Ok("NaN".to_string()),
+        0xD000 => return Ok("Infinity".to_string()),
+        0xF000 => return Ok("-Infinity".to_string()),
+        _ => return Err("invalid numeric sign".to_string()),
+    }
+    
+    let mut text = String::new();
+    if sign == 0x4000 {
+        text.push('-');
+    }
+    if weight < 0 {
+        text.push('0');
+    } else {
+        let _ = write!(text, "{}", digit(0));
+        for index in 1..=weight {
+            let _ = write!(text, "{:04}", digit(index));
+        }
+    }
+    if dscale > 0 {
+        let mut fraction = String::new();
+        for position in 1..=dscale.div_ceil(4) as i32 {
+            let _ = write!(fraction, "{:04}", digit(weight + position));
+        }
+        fraction.truncate(dscale);
+        text.push('.');
+        text.push_str(&fraction);
+    }
+    Ok(text)
+}
@@ -0,0 +1,237 @@
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+
+/// 保留的查询参数，其余参数是结果中的列名时作为过滤条件
+const RESERVED: [&str; 4] = ["fields", "sort", "limit", "offset"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+#[derive(Debug)]
+struct Filter {
+    field: String,
+    op: Op,
+    /// 同一个字段的多个等值条件是“或”的关系
+    values: Vec<String>,
+}
+
+/// serve模式下对数组结果的过滤、排序、投影和分页
+///
+/// - `region=EU`：等值，同一字段多次出现时匹配任意一个；不是结果中的列名的参数
+///   （如缓存参数`_=1699999999`、`utm_source`）被忽略
+/// - `amount[gte]=100`：范围，支持`ne`、`gt`、`gte`、`lt`、`lte`；不支持的运算符只在字段是列名时报错
+/// - `sort=-amount,name`：排序，`-`表示降序
+/// - `fields=id,name`：只返回这些列
+/// - `limit=20&offset=40`：分页
+#[derive(Debug, Default)]
+pub struct PayloadQuery {
+    filters: Vec<Filter>,
+    /// 不支持的运算符，`(字段, 参数名)`，字段是列名时在`apply`中报错
+    unsupported: Vec<(String, String)>,
+    sort: Vec<(String, bool)>,
+    fields: Option<Vec<String>>,
+    limit: Option<usize>,
+    offset: usize,
+}
+
+impl PayloadQuery {
+    /// 没有查询参数时返回None，直接返回原始内容
+    pub fn parse(params: &[(String, String)]) -> Result<Option<Self>, String> {
+        if params.is_empty() {
+            return Ok(None);
+        }
+        
+        let mut query = PayloadQuery::default();
+        for (name, value) in params {
+            match name.as_str() {
+                "fields" => query.fields = Some(split_list(value)),
+                "sort" => query.sort = split_list(value).into_iter()
+                    .map(|field| match field.strip_prefix('-') {
+                        Some(field) => (field.to_string(), true),
+                        None => (field.trim_start_matches('+').to_string(), false),
+                    })
+                    .collect(),
+                "limit" => query.limit = Some(parse_number(name, value)?),
+                "offset" => query.offset = parse_number(name, value)?,
+                _ => query.add_filter(name, value)?,
+            }
+        }
+        Ok(Some(query))
+    }
+    
+    fn add_filter(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let (field, op) = match name.split_once('[') {
+            Some((field, op)) => {
+                let op = match op.strip_suffix(']') {
+                    Some("eq") => Op::Eq,
+                    Some("ne") => Op::Ne,
+                    Some("gt") => Op::Gt,
+                    Some("gte") => Op::Gte,
+                    Some("lt") => Op::Lt,
+                    Some("lte") => Op::Lte,
+                    _ => {
+                        self.unsupported.push((field.to_string(), name.to_string()));
+                        return Ok(());
+                    }
+                };
+                (field, op)
+            }
+            None => (name, Op::Eq),
+        };
+        if field.is_empty() || RESERVED.contains(&field) {
+            return Err(format!("unsupported filter '{}'", name));
+        }
+        
+        match self.filters.iter_mut().find(|filter| filter.field == field && filter.op == op && op == Op::Eq) {
+            Some(filter) => filter.values.push(value.to_string()),
+            None => self.filters.push(Filter { field: field.to_string(), op, values: vec![value.to_string()] }),
+        }
+        Ok(())
+    }
+    
+    /// 去掉字段不是结果中任何一行的列名的过滤条件，结果不是数组时不变
+    pub fn ignore_unknown_filters(&mut self, value: &Value) {
+        let Ok((_, rows)) = array_rows(value) else {
+            return;
+        };
+        self.filters.retain(|filter| {
+            let known = rows.iter().any(|row| row.get(&filter.field).is_some());
+            if !known {
+                log::debug!("Ignoring query parameter '{}', not a column", filter.field);
+            }
+            known
+        });
+        self.unsupported.retain(|(field, name)| {
+            let known = rows.iter().any(|row| row.get(field).is_some());
+            if !known {
+                log::debug!("Ignoring query parameter '{}', not a column", name);
+            }
+            known
+        });
+    }
+    
+    /// 没有任何过滤、排序、投影或分页
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty() && self.unsupported.is_empty() && self.sort.is_empty() && self.fields.is_none() && self.limit.is_none() && self.offset == 0
+    }
+    
+    /// 对结果中的数组执行查询，保留原来的结构（如`{"kpi": [...]}`）
+    ///
+    /// 返回查询结果和分页前的行数
+    pub fn apply(&self, value: &Value) -> Result<(Value, usize), String> {
+        if let Some((_, name)) = self.unsupported.first() {
+            return Err(format!("unsupported filter '{}'", name));
+        }
+        let (root, rows) = array_rows(value)?;
+        
+        let mut rows: Vec<&Value> = rows.iter().filter(|row| self.matches(row)).collect();
+        let total = rows.len();
+        
+        if !self.sort.is_empty() {
+            rows.sort_by(|a, b| {
+                self.sort.iter()
+                    .map(|(field, descending)| {
+                        let ordering = compare_values(a.get(field), b.get(field));
+                        if *descending { ordering.reverse() } else { ordering }
+                    })
+                    .find(|ordering| ordering.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            });
+        }
+        
+        let rows: Vec<Value> = rows.into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .map(|row| self.project(row))
+            .collect();
+        
+        let result = match root {
+            Some(name) => {
+                let mut object = Map::new();
+                object.insert(name.to_string(), Value::Array(rows));
+                Value::Object(object)
+            }
+            None => Value::Array(rows),
+        };
+        Ok((result, total))
+    }
+    
+    fn matches(&self, row: &Value) -> bool {
+        self.filters.iter().all(|filter| {
+            let value = row.get(&filter.field);
+            match filter.op {
+                Op::Eq => filter.values.iter().any(|expected| compare_to(value, expected) == Some(Ordering::Equal)),
+                Op::Ne => filter.values.iter().all(|expected| compare_to(value, expected) != Some(Ordering::Equal)),
+                Op::Gt => compare_to(value, &filter.values[0]).is_some_and(|o| o.is_gt()),
+                Op::Gte => compare_to(value, &filter.values[0]).is_some_and(|o| o.is_ge()),
+                Op::Lt => compare_to(value, &filter.values[0]).is_some_and(|o| o.is_lt()),
+                Op::Lte => compare_to(value, &filter.values[0]).is_some_and(|o| o.is_le()),
+            }
+        })
+    }
+    
+    fn project(&self, row: &Value) -> Value {
+        match (&self.fields, row) {
+            (Some(fields), Value::Object(object)) => Value::Object(
+                fields.iter()
+                    .filter_map(|field| object.get(field).map(|value| (field.clone(), value.clone())))
+                    .collect()
+            ),
+            _ => row.clone(),
+        }
+    }
+}
+
+/// 结果中的数组以及外层对象的键
+fn array_rows(value: &Value) -> Result<(Option<&str>, &Vec<Value>), String> {
+    match value {
+        Value::Array(rows) => Ok((None, rows)),
+        Value::Object(object) => match object.iter().collect::<Vec<_>>().as_slice() {
+            [(name, Value::Array(rows))] => Ok((Some(name.as_str()), rows)),
+            _ => Err("query parameters require an array-shaped payload".to_string()),
+        },
+        _ => Err("query parameters require an array-shaped payload".to_string()),
+    }
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value.split(',')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+fn parse_number(name: &str, value: &str) -> Result<usize, String> {
+    value.parse().map_err(|_| format!("invalid {} '{}'", name, value))
+}
+
+/// 把JSON值与查询参数中的字符串比较：数值按数值比较，其余按字符串比较（适用于ISO日期）
+fn compare_to(value: Option<&Value>, expected: &str) -> Option<Ordering> {
+    match value? {
+        Value::Number(number) => number.as_f64()?.partial_cmp(&expected.parse::<f64>().ok()?),
+        Value::String(text) => Some(text.as_str().cmp(expected)),
+        Value::Bool(flag) => Some(flag.to_string().as_str().cmp(expected)),
+        Value::Null => (expected == "null").then_some(Ordering::Equal),
+        _ => None,
+    }
+}
+
+/// 排序时缺失和null排在最前
+fn compare_values(a: Option<&Value>, b: Option<&Value>) -> Ordering {
+    match (a, b) {
+        (Some(Value::Number(a)), Some(Value::Number(b))) => a.as_f64().partial_cmp(&b.as_f64()).unwrap_or(Ordering::Equal),
+        (Some(Value::String(a)), Some(Value::String(b))) => a.cmp(b),
+        (Some(Value::Bool(a)), Some(Value::Bool(b))) => a.cmp(b),
+        (None | Some(Value::Null), None | Some(Value::Null)) => Ordering::Equal,
+        (None | Some(Value::Null), _) => Ordering::Less,
+        (_, None | Some(Value::Null)) => Ordering::Greater,
+        (a, b) => a.map(|a| a.to_string()).cmp(&b.map(|b| b.to_string())),
+    }
+}
@@ -0,0 +1,227 @@
+use hmac::{Hmac, Mac};
+use log::info;
+use regex::Regex;
+use serde_json::{Map, Value};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::sync::LazyLock;
+
+use crate::config::{RedactionAction, RedactionConfig};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const EMAIL_PATTERN: &str = r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}";
+/// 候选的电话号码，再按数字个数过滤
+const PHONE_PATTERN: &str = r"\+?\(?\d[\d\s().-]{6,}\d";
+
+/// 每条规则在每一列处理的值的个数，键为(策略名称, 列名)
+pub type RedactionCounts = BTreeMap<(String, String), usize>;
+
+static EMAIL: LazyLock<Regex> = LazyLock::new(|| Regex::new(EMAIL_PATTERN).expect("valid email pattern"));
+static PHONE: LazyLock<Regex> = LazyLock::new(|| Regex::new(PHONE_PATTERN).expect("valid phone pattern"));
+static WHOLE_EMAIL: LazyLock<Regex> = LazyLock::new(|| Regex::new(&format!("^{}$", EMAIL_PATTERN)).expect("valid email pattern"));
+static DATE_PREFIX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\d{4}-\d{2}-\d{2}").expect("valid date pattern"));
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PatternKind {
+    Email,
+    Phone,
+    Custom,
+}
+
+#[derive(Debug)]
+struct Rule {
+    name: String,
+    columns: Vec<glob::Pattern>,
+    pattern: Option<(PatternKind, Regex)>,
+    action: RedactionAction,
+}
+
+impl Rule {
+    fn matches_column(&self, column: &str) -> bool {
+        let column = column.to_lowercase();
+        self.columns.is_empty() || self.columns.iter().any(|pattern| pattern.matches(&column))
+    }
+}
+
+/// 由[`RedactionConfig`]编译得到的脱敏规则，按配置的顺序依次执行
+#[derive(Debug, Default)]
+pub struct Redactor {
+    rules: Vec<Rule>,
+    salt: Vec<u8>,
+}
+
+impl Redactor {
+    pub fn new(config: &RedactionConfig) -> Result<Self, String> {
+        let salt = config.salt.as_ref().map(|salt| salt.expose().as_bytes().to_vec()).unwrap_or_default();
+        let mut rules = Vec::new();
+        for (i, rule) in config.rules.iter().enumerate() {
+            let name = rule.name.clone().unwrap_or_else(|| format!("rules[{}]", i));
+            if rule.columns.is_empty() && rule.pattern.is_none() {
+                return Err(format!("{}: set columns, pattern or both", name));
+            }
+            if matches!(rule.action, RedactionAction::Hash | RedactionAction::Tokenize) && salt.is_empty() {
+                return Err(format!("{}: action \"{:?}\" requires a salt", name, rule.action).to_lowercase());
+            }
+            let columns = rule.columns.iter()
+                .map(|column| glob::Pattern::new(&column.to_lowercase()).map_err(|e| format!("{}: columns '{}': {}", name, column, e)))
+                .collect::<Result<_, _>>()?;
+            let pattern = match rule.pattern.as_deref() {
+                None => None,
+                Some("email") => Some((PatternKind::Email, EMAIL.clone())),
+                Some("phone") => Some((PatternKind::Phone, PHONE.clone())),
+                Some(pattern) => Some((PatternKind::Custom, Regex::new(pattern).map_err(|e| format!("{}: pattern: {}", name, e))?)),
+            };
+            rules.push(Rule { name, columns, pattern, action: rule.action });
+        }
+        Ok(Redactor { rules, salt })
+    }
+    
+    /// 脱敏一行，NULL不处理
+    pub fn redact(&self, row: &mut Map<String, Value>, counts: &mut RedactionCounts) {
+        for rule in &self.rules {
+            let columns: Vec<String> = row.keys().filter(|column| rule.matches_column(column)).cloned().collect();
+            for column in columns {
+                let Some(value) = row.get_mut(&column) else {
+                    continue;
+                };
+                if value.is_null() {
+                    continue;
+                }
+                let touched = match (&rule.pattern, rule.action) {
+                    (None, RedactionAction::Drop) => true,
+                    (None, action) => {
+                        *value = self.redact_value(value, action);
+                        true
+                    }
+                    (Some(pattern), RedactionAction::Drop) => self.contains_match(value, pattern),
+                    (Some(pattern), action) => self.replace_matches(value, pattern, action) > 0,
+                };
+                if touched {
+                    if rule.action == RedactionAction::Drop {
+                        row.remove(&column);
+                    }
+                    *counts.entry((rule.name.clone(), column)).or_default() += 1;
+                }
+            }
+        }
+    }
+    
+    /// 记录每条规则处理了哪些列，不记录值
+    pub fn log_counts(label: &str, counts: &RedactionCounts) {
+        for ((policy, column), count) in counts {
+            info!(
+                file = label, phase = "redact", policy = policy.as_str(), column = column.as_str(), count;
+                "Redacted {} value(s) in column {} of {} with policy {}", count, column, label, policy
+            );
+        }
+    }
+    
+    /// 按列脱敏整个值，数字和布尔值按文本处理，JSON对象和数组按JSON文本处理
+    fn redact_value(&self, value: &Value, action: RedactionAction) -> Value {
+        match value {
+            Value::String(text) => Value::String(self.replace_text(text, action, None)),
+            Value::Array(_) | Value::Object(_) if action == RedactionAction::Mask => Value::String("***".to_string()),
+            other => Value::String(self.replace_text(&other.to_string(), action, None)),
+        }
+    }
+    
+    fn replace_text(&self, text: &str, action: RedactionAction, kind: Option<PatternKind>) -> String {
+        match action {
+            RedactionAction::Hash => self.hmac_hex(text),
+            RedactionAction::Tokenize => format!("tok_{}", &self.hmac_hex(text)[..16]),
+            _ => mask(text, kind),
+        }
+    }
+    
+    fn hmac_hex(&self, text: &str) -> String {
+        let mut mac = HmacSha256::new_from_slice(&self.salt).expect("HMAC accepts keys of any length");
+        mac.update(text.as_bytes());
+        format!("{:x}", mac.finalize().into_bytes())
+    }
+    
+    fn find_matches(text: &str, (kind, regex): &(PatternKind, Regex)) -> Vec<Range<usize>> {
+        regex.find_iter(text)
+            .filter(|found| *kind != PatternKind::Phone || looks_like_phone(found.as_str()))
+            .map(|found| found.range())
+            .collect()
+    }
+    
+    fn contains_match(&self, value: &Value, pattern: &(PatternKind, Regex)) -> bool {
+        match value {
+            Value::String(text) => !Self::find_matches(text, pattern).is_empty(),
+            Value::Number(number) => !Self::find_matches(&number.to_string(), pattern).is_empty(),
+            Value::Array(items) => items.iter().any(|item| self.contains_match(item, pattern)),
+            Value::Object(fields) => fields.values().any(|field| self.contains_match(field, pattern)),
+            _ => false,
+        }
+    }
+    
+    /// 替换字符串中匹配的部分，返回替换的个数；数字按文本匹配（纯数字的VARCHAR会被读成整数），替换后变为字符串
+    fn replace_matches(&self, value: &mut Value, pattern: &(PatternKind, Regex), action: RedactionAction) -> usize {
+        match value {
+            Value::String(text) => match self.replace_in_text(text, pattern, action) {
+                Some((replaced, count)) => {
+                    *text = replaced;
+                    count
+                }
+                None => 0,
+            },
+            Value::Number(number) => match self.replace_in_text(&number.to_string(), pattern, action) {
+                Some((replaced, count)) => {
+                    *value = Value::String(replaced);
+                    count
+                }
+                None => 0,
+            },
+            Value::Array(items) => items.iter_mut().map(|item| self.replace_matches(item, pattern, action)).sum(),
+            Value::Object(fields) => fields.values_mut().map(|field| self.replace_matches(field, pattern, action)).sum(),
+            _ => 0,
+        }
+    }
+    
+    /// 没有匹配时返回None
+    fn replace_in_text(&self, text: &str, pattern: &(PatternKind, Regex), action: RedactionAction) -> Option<(String, usize)> {
+        let matches = Self::find_matches(text, pattern);
+        if matches.is_empty() {
+            return None;
+        }
+        let mut replaced = String::with_capacity(text.len());
+        let mut last = 0;
+        for range in &matches {
+            replaced.push_str(&text[last..range.start]);
+            replaced.push_str(&self.replace_text(&text[range.clone()], action, Some(pattern.0)));
+            last = range.end;
+        }
+        replaced.push_str(&text[last..]);
+        Some((replaced, matches.len()))
+    }
+}
+
+/// 9到15位数字，排除`2024-01-02 10`这样的日期
+fn looks_like_phone(text: &str) -> bool {
+    let digits = text.chars().filter(char::is_ascii_digit).count();
+    (9..=15).contains(&digits) && !DATE_PREFIX.is_match(text)
+}
+
+/// 邮箱保留首字母和域名，电话号码保留最后4位，其他文本保留首字符
+fn mask(text: &str, kind: Option<PatternKind>) -> String {
+    let is_email = kind == Some(PatternKind::Email)
+        || (kind.is_none() && WHOLE_EMAIL.is_match(text));
+    let digits: Vec<char> = text.chars().filter(char::is_ascii_digit).collect();
+    let is_phone = kind == Some(PatternKind::Phone)
+        || (kind.is_none() && digits.len() >= 7 && text.chars().all(|c| c.is_ascii_digit() || " +-().".contains(c)));
+    
+    if is_email && let Some((local, domain)) = text.rsplit_once('@') {
+        let first = local.chars().next().map(String::from).unwrap_or_default();
+        return format!("{}***@{}", first, domain);
+    }
+    if is_phone {
+        return format!("***{}", digits[digits.len().saturating_sub(4)..].iter().collect::<String>());
+    }
+    match text.chars().next() {
+        Some(first) if text.chars().count() > 1 => format!("{}***", first),
+        _ => "***".to_string(),
+    }
+}
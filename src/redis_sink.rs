@@ -0,0 +1,50 @@
+use crate::config::RedisConfig;
+use crate::sink::{expand_template, OutputSink, PublishedPayload};
+use std::time::Duration;
+
+/// 把JSON结果同时写入Redis（双写）
+pub struct RedisSink {
+    client: redis::Client,
+    config: RedisConfig,
+}
+
+impl RedisSink {
+    pub fn new(config: &RedisConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let client = redis::Client::open(config.url.expose())?;
+        Ok(RedisSink {
+            client,
+            config: config.clone(),
+        })
+    }
+    
+    /// 由模板生成键名，支持`{path}`（`subdir/kpi.json`）和`{stem}`（`kpi`）
+    pub fn key_for(&self, url_path: &str) -> String {
+        expand_template(&self.config.key_template, url_path)
+    }
+}
+
+impl OutputSink for RedisSink {
+    /// `SET`结果（可带TTL），内容变化且配置了频道时`PUBLISH`变更通知
+    fn publish(&self, payload: &PublishedPayload) -> Result<(), Box<dyn std::error::Error>> {
+        let mut conn = self.client.get_connection_with_timeout(Duration::from_secs(self.config.timeout_seconds))?;
+        let key = self.key_for(payload.url_path);
+        
+        let mut set = redis::cmd("SET");
+        set.arg(&key).arg(payload.content);
+        if let Some(ttl) = self.config.ttl_seconds.filter(|ttl| *ttl > 0) {
+            set.arg("EX").arg(ttl);
+        }
+        set.query::<()>(&mut conn)?;
+        
+        if payload.changed && let Some(channel) = &self.config.publish_channel {
+            let message = serde_json::json!({
+                "path": payload.url_path,
+                "key": key,
+                "sha256": payload.hash,
+            });
+            redis::cmd("PUBLISH").arg(channel).arg(message.to_string()).query::<i64>(&mut conn)?;
+        }
+        
+        Ok(())
+    }
+}
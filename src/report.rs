@@ -0,0 +1,143 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::path::Path;
+
+use crate::db::QueryTarget;
+use crate::file_handler;
+
+/// 所有文件都成功（或未到更新时间）
+pub const EXIT_OK: i32 = 0;
+/// 命令行参数、配置、数据库连接等致命错误，没有处理任何文件
+pub const EXIT_FATAL: i32 = 1;
+/// 至少一个文件失败
+pub const EXIT_PARTIAL_FAILURE: i32 = 2;
+
+/// 单个SQL文件的处理状态
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FileStatus {
+    /// 执行了SQL并发布了JSON
+    Generated,
+    /// 没有执行SQL
+    Skipped,
+    /// 处理失败，旧的JSON保持不变
+    Failed,
+}
+
+/// 单个SQL文件的处理结果
+#[derive(Serialize, Debug, Clone)]
+pub struct FileReport {
+    /// SQL文件相对于start_dir的路径
+    pub path: String,
+    /// 处理状态
+    pub status: FileStatus,
+    /// 跳过的原因，如`not due`、`paused`、`locked`
+    pub reason: Option<String>,
+    /// 处理耗时（毫秒）
+    pub duration_ms: u64,
+    /// 结果的行数
+    pub rows: Option<usize>,
+    /// JSON的字节数
+    pub bytes: Option<usize>,
+    /// 内容与上一次发布的不同
+    pub changed: Option<bool>,
+    /// 本地JSON文件路径（使用file输出时）
+    pub output: Option<String>,
+    /// 失败的分类，如`sql`、`guard`、`sink`
+    pub error_class: Option<&'static str>,
+    /// 失败的原因
+    pub error: Option<String>,
+    /// 执行SQL的连接、服务器和复制延迟
+    pub target: Option<QueryTarget>,
+}
+
+/// 一次运行的报告
+#[derive(Serialize, Debug)]
+pub struct RunReport {
+    /// 开始时间
+    pub started_at: DateTime<Utc>,
+    /// 结束时间
+    pub finished_at: DateTime<Utc>,
+    /// 总耗时（毫秒）
+    pub duration_ms: u64,
+    /// 生成的文件数
+    pub generated: usize,
+    /// 跳过的文件数
+    pub skipped: usize,
+    /// 失败的文件数
+    pub failed: usize,
+    /// 每个文件的结果，按路径排序
+    pub files: Vec<FileReport>,
+}
+
+impl RunReport {
+    /// 汇总各文件的结果，结束时间为当前时间
+    pub fn new(started_at: DateTime<Utc>, mut files: Vec<FileReport>) -> Self {
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        let finished_at = Utc::now();
+        let count = |status| files.iter().filter(|file| file.status == status).count();
+        RunReport {
+            started_at,
+            finished_at,
+            duration_ms: (finished_at - started_at).num_milliseconds().max(0) as u64,
+            generated: count(FileStatus::Generated),
+            skipped: count(FileStatus::Skipped),
+            failed: count(FileStatus::Failed),
+            files,
+        }
+    }
+    
+    /// 进程的退出码
+    pub fn exit_code(&self) -> i32 {
+        if self.failed > 0 { EXIT_PARTIAL_FAILURE } else { EXIT_OK }
+    }
+    
+    /// 以JSON格式原子写入报告文件
+    pub fn write(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        file_handler::write_file_atomic(path, serde_json::to_string_pretty(self)?.as_bytes())
+    }
+    
+    /// 终端中显示的汇总表
+    pub fn summary_table(&self) -> String {
+        let header = ["FILE", "STATUS", "ROWS", "BYTES", "DURATION", "DETAIL"].map(String::from);
+        let rows: Vec<[String; 6]> = self.files.iter().map(|file| [
+            file.path.clone(),
+            match file.status {
+                FileStatus::Generated if file.changed == Some(false) => "unchanged".to_string(),
+                FileStatus::Generated => "generated".to_string(),
+                FileStatus::Skipped => "skipped".to_string(),
+                FileStatus::Failed => "FAILED".to_string(),
+            },
+            file.rows.map_or("-".to_string(), |rows| rows.to_string()),
+            file.bytes.map_or("-".to_string(), |bytes| bytes.to_string()),
+            format!("{}ms", file.duration_ms),
+            match (&file.reason, &file.error) {
+                (_, Some(error)) => format!("[{}] {}", file.error_class.unwrap_or("other"), error.lines().next().unwrap_or("")),
+                (Some(reason), None) => reason.clone(),
+                (None, None) => file.output.clone().unwrap_or_default(),
+            },
+        ]).collect();
+        
+        let mut widths = header.clone().map(|column| column.len());
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        
+        let mut table = String::new();
+        for row in std::iter::once(&header).chain(&rows) {
+            let line = row.iter().zip(widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join("  ");
+            table.push_str(line.trim_end());
+            table.push('\n');
+        }
+        table.push_str(&format!(
+            "{} generated, {} skipped, {} failed in {}ms\n",
+            self.generated, self.skipped, self.failed, self.duration_ms
+        ));
+        table
+    }
+}
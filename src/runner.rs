@@ -0,0 +1,663 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use tokio::task::{self, JoinError, JoinSet};
+use log::error;
+
+use crate::backend::Backend;
+use crate::cache::PayloadStore;
+use crate::config::{Config, LockBackend, SinkPolicy, DEFAULT_DATABASE};
+use crate::db::{DbPool, DbPools};
+use crate::file_handler::{self, FileHandler, Precompressed};
+use crate::guard;
+use crate::hooks::{FailureEvent, Hooks, PublishEvent};
+use crate::json_generator::JsonGenerator;
+use crate::lock;
+use crate::metrics::{self, Metrics};
+use crate::report::{FileReport, FileStatus, RunReport};
+use crate::sink::{self, OutputSink, PublishedPayload, SinkError, FILE_SINK};
+use crate::logger::log_error;
+use crate::redaction::Redactor;
+use crate::scanner::{self, scan_sql_files};
+use crate::sql_executor::SqlExecutor;
+use crate::state::{FileState, StateStore};
+use crate::status::{RunOutcome, RunRecord, StatusBoard};
+use crate::transform::Transform;
+
+/// 扫描、执行SQL并发布JSON的完整流程，单次运行和serve模式共用
+pub struct Runner {
+    pipeline: RwLock<Arc<Pipeline>>,
+    sql_executor: SqlExecutor,
+    json_generator: JsonGenerator,
+    state_store: StateStore,
+    payload_store: Option<Arc<PayloadStore>>,
+    status: StatusBoard,
+    metrics: Metrics,
+    hooks: Hooks,
+}
+
+/// 创建[`Runner`]，可以替换默认的数据库连接并注册发布和失败回调
+pub struct RunnerBuilder {
+    config: Config,
+    backend: Option<Arc<dyn Backend>>,
+    payload_store: Option<Arc<PayloadStore>>,
+    hooks: Hooks,
+}
+
+impl RunnerBuilder {
+    /// 使用已有的数据库连接代替`[database]`，如服务自己的连接池或测试替身
+    pub fn backend(mut self, backend: Arc<dyn Backend>) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+    
+    /// 生成的JSON同时保存到内存中，供内置HTTP服务使用
+    pub fn payload_store(mut self, payload_store: Arc<PayloadStore>) -> Self {
+        self.payload_store = Some(payload_store);
+        self
+    }
+    
+    /// JSON发布到所有输出之后调用
+    pub fn on_publish<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&PublishEvent) + Send + Sync + 'static,
+    {
+        self.hooks.on_publish.push(Arc::new(hook));
+        self
+    }
+    
+    /// SQL文件处理失败时调用
+    pub fn on_failure<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&FailureEvent) + Send + Sync + 'static,
+    {
+        self.hooks.on_failure.push(Arc::new(hook));
+        self
+    }
+    
+    /// 连接数据库并加载状态文件；连接数据库是阻塞操作，在异步代码中需要放到
+    /// `spawn_blocking`或`block_in_place`中
+    pub fn build(self) -> Result<Runner, Box<dyn std::error::Error>> {
+        let config = self.config;
+        let injected = self.backend.is_some();
+        
+        // 创建默认连接和命名连接的连接池
+        let db_pools = DbPools::new(&config, self.backend)?;
+        
+        // 获取数据库版本
+        let version = db_pools.default_backend().version()?;
+        log::info!("Database version: {}", version);
+        
+        // 创建各个组件
+        let state_store = StateStore::load(config.state_file_path());
+        let status = StatusBoard::new(config.admin.as_ref().map_or(20, |admin| admin.history_size));
+        // 注入的数据库代替了[database]，需要MySQL连接的锁和输出在这里就报错，而不是运行时才失败
+        let pipeline = Pipeline::new(config, db_pools.mysql_pool()).map_err(|e| {
+            if injected { format!("{} (the injected backend replaces [database])", e).into() } else { e }
+        })?;
+        let sql_executor = SqlExecutor::new(db_pools);
+        let json_generator = JsonGenerator::new(version);
+        
+        Ok(Runner {
+            pipeline: RwLock::new(Arc::new(pipeline)),
+            sql_executor,
+            json_generator,
+            state_store,
+            payload_store: self.payload_store,
+            status,
+            metrics: Metrics::new(),
+            hooks: self.hooks,
+        })
+    }
+}
+
+/// 由配置决定、重新加载配置时整体替换的部分，处理中的文件继续使用旧的配置
+struct Pipeline {
+    config: Arc<Config>,
+    file_handler: Arc<FileHandler>,
+    sinks: HashMap<String, Arc<dyn OutputSink>>,
+    redactor: Redactor,
+    /// 按`[files]`的键保存编译好的行转换，只在加载配置时编译一次
+    transforms: HashMap<String, Transform>,
+}
+
+impl Pipeline {
+    fn new(config: Config, pool: Option<&DbPool>) -> Result<Self, Box<dyn std::error::Error>> {
+        if config.app.lock_backend == LockBackend::Mysql && pool.is_none() {
+            return Err("lock_backend = \"mysql\" requires a MySQL [database]".into());
+        }
+        let file_handler = Arc::new(FileHandler::from_config(&config));
+        let sinks = sink::build_sinks(&config, file_handler.clone(), pool)?;
+        let redactor = match &config.redaction {
+            Some(redaction) => Redactor::new(redaction)?,
+            None => Redactor::default(),
+        };
+        let mut transforms = HashMap::new();
+        for (path, file_config) in config.files.iter().flatten() {
+            if let Some(transform) = &file_config.transform {
+                transforms.insert(path.clone(), Transform::new(transform)?);
+            }
+        }
+        Ok(Pipeline {
+            config: Arc::new(config),
+            file_handler,
+            sinks,
+            redactor,
+            transforms,
+        })
+    }
+}
+
+/// 管理接口中单个SQL文件的状态
+#[derive(Serialize)]
+pub struct QueryStatus {
+    /// SQL文件相对于start_dir的路径
+    pub path: String,
+    /// 内置HTTP服务中的路径
+    pub url_path: String,
+    /// 更新间隔（分钟），为空表示每次运行都会检查
+    pub interval_minutes: Option<u64>,
+    /// 是否已通过管理接口暂停
+    pub paused: bool,
+    /// 最近一次执行
+    pub last_run: Option<RunRecord>,
+    /// 最近一次成功生成的时间
+    pub last_success_at: Option<DateTime<Utc>>,
+    /// 为空表示每次运行都会检查
+    pub next_due_at: Option<DateTime<Utc>>,
+    /// 最近一次失败
+    pub last_error: Option<RunRecord>,
+}
+
+impl Runner {
+    /// 使用`[database]`和默认选项创建，等同于`Runner::builder(config).build()`
+    pub fn new(config: Config) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::builder(config).build()
+    }
+    
+    /// 从配置开始创建，可以注入数据库、共享的内容存储和回调
+    pub fn builder(config: Config) -> RunnerBuilder {
+        RunnerBuilder {
+            config,
+            backend: None,
+            payload_store: None,
+            hooks: Hooks::default(),
+        }
+    }
+    
+    /// 当前生效的配置
+    pub fn config(&self) -> Arc<Config> {
+        self.pipeline().config.clone()
+    }
+    
+    fn pipeline(&self) -> Arc<Pipeline> {
+        self.pipeline.read().unwrap().clone()
+    }
+    
+    /// 使用新的配置，数据库连接和状态文件保持不变
+    pub fn reload(&self, config: Config) -> Result<(), Box<dyn std::error::Error>> {
+        let pipeline = Pipeline::new(config, self.sql_executor.mysql_pool())?;
+        *self.pipeline.write().unwrap() = Arc::new(pipeline);
+        log::info!("Configuration reloaded");
+        Ok(())
+    }
+    
+    /// start_dir下的所有SQL文件
+    pub fn sql_files(&self) -> Vec<PathBuf> {
+        scan_sql_files(&self.config().app.start_dir)
+    }
+    
+    /// 相对路径与glob模式匹配的SQL文件，如`subdir/*.sql`
+    pub fn matching_files(&self, pattern: &str) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+        let pattern = glob::Pattern::new(pattern)?;
+        Ok(self.sql_files().into_iter()
+            .filter(|sql_file| pattern.matches(&self.relative_path(sql_file)))
+            .collect())
+    }
+    
+    /// 暂停或恢复定时生成，手动刷新不受影响
+    pub fn set_paused(&self, sql_file: &Path, paused: bool) {
+        self.status.set_paused(&self.relative_path(sql_file), paused);
+    }
+    
+    /// 所有SQL文件最近的执行情况
+    pub fn status(&self) -> Vec<QueryStatus> {
+        let config = self.config();
+        self.sql_files().iter().map(|sql_file| {
+            let path = self.relative_path(sql_file);
+            let interval = config.get_interval(&sql_file.to_string_lossy());
+            let history = self.status.history(&path);
+            let last_success_at = self.state_store.get(&path).map(|state| state.generated_at);
+            QueryStatus {
+                url_path: self.url_path(sql_file),
+                interval_minutes: interval,
+                paused: self.status.is_paused(&path),
+                last_run: history.first().cloned(),
+                last_success_at,
+                next_due_at: interval.zip(last_success_at)
+                    .map(|(interval, at)| at + chrono::Duration::minutes(interval as i64)),
+                last_error: history.into_iter().find(|record| record.error.is_some()),
+                path,
+            }
+        }).collect()
+    }
+    
+    /// SQL文件最近的执行记录，最新的在前
+    pub fn history(&self, sql_file: &Path) -> Vec<RunRecord> {
+        self.status.history(&self.relative_path(sql_file))
+    }
+    
+    /// 执行SQL并返回各行组成的JSON数组（已按配置脱敏），不写入任何输出；
+    /// SQL开头可以用`-- @database: <name>`选择命名连接
+    pub fn render_query(&self, sql: &str) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+        let database = SqlExecutor::directive(sql, "database").unwrap_or(DEFAULT_DATABASE);
+        let (results, _) = self.sql_executor.execute_sql("<query>", sql, database, None)?;
+        Ok(JsonGenerator::rows_value(&results, &Transform::default(), &self.pipeline().redactor, "<query>")?)
+    }
+    
+    /// 扫描start_dir并并发处理所有SQL文件，返回本轮的运行报告
+    pub async fn run_once(self: &Arc<Self>) -> RunReport {
+        let started_at = Utc::now();
+        
+        // 扫描SQL文件
+        let start_dir = self.config().app.start_dir.clone();
+        let sql_files = scan_sql_files(&start_dir);
+        log::info!("Found {} SQL files in directory: {}", sql_files.len(), start_dir);
+        
+        let mut tasks = JoinSet::new();
+        let mut reports = Vec::new();
+        let max_concurrent = 5;
+        self.metrics.queue_depth.store(sql_files.len(), Ordering::Relaxed);
+        
+        // 任务panic或被取消时，按任务ID找到对应的SQL文件，记为失败
+        let mut spawned = HashMap::new();
+        for sql_file in sql_files {
+            let runner = self.clone();
+            
+            // 数据库和文件操作都是阻塞的，放到阻塞线程池中执行
+            self.metrics.queue_depth.fetch_sub(1, Ordering::Relaxed);
+            let task_file = sql_file.clone();
+            let handle = tasks.spawn_blocking(move || {
+                runner.metrics.running.fetch_add(1, Ordering::Relaxed);
+                let report = runner.run_file(&task_file, false);
+                runner.metrics.running.fetch_sub(1, Ordering::Relaxed);
+                report
+            });
+            spawned.insert(handle.id(), (sql_file, Utc::now(), Instant::now()));
+            
+            // 限制并发任务数
+            if tasks.len() >= max_concurrent
+                && let Some(joined) = tasks.join_next_with_id().await {
+                reports.push(self.joined_report(joined, &mut spawned));
+            }
+        }
+        
+        // 等待所有任务完成
+        while let Some(joined) = tasks.join_next_with_id().await {
+            reports.push(self.joined_report(joined, &mut spawned));
+        }
+        let report = RunReport::new(started_at, reports);
+        
+        if let Some(report_file) = self.config().app.report_file.clone() {
+            let result = report.write(Path::new(&report_file)).map_err(|e| e.to_string());
+            if let Err(e) = result {
+                log::error!("Failed to write run report {}: {}", report_file, e);
+            }
+        }
+        
+        // 没有HTTP服务时，指标写到node_exporter的textfile目录
+        if let Some(textfile) = self.config().metrics.as_ref().and_then(|metrics| metrics.textfile.clone()) {
+            let runner = self.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                file_handler::write_file_atomic(Path::new(&textfile), runner.render_metrics().as_bytes()).map_err(|e| e.to_string())
+            }).await;
+            if let Ok(Err(e)) = result {
+                log::error!("Failed to write metrics textfile: {}", e);
+            }
+        }
+        report
+    }
+    
+    /// 任务正常结束时返回它的报告，panic或被取消时为对应的SQL文件生成失败的报告
+    fn joined_report(
+        &self,
+        joined: Result<(task::Id, FileReport), JoinError>,
+        spawned: &mut HashMap<task::Id, (PathBuf, DateTime<Utc>, Instant)>
+    ) -> FileReport {
+        let e = match joined {
+            Ok((id, report)) => {
+                spawned.remove(&id);
+                return report;
+            }
+            Err(e) => e,
+        };
+        let (sql_file, started_at, start) = spawned.remove(&e.id()).expect("every task is spawned for a SQL file");
+        if e.is_panic() {
+            self.metrics.running.fetch_sub(1, Ordering::Relaxed);
+        }
+        let message = format!("Task failed: {}", e);
+        self.report_file(&sql_file, false, started_at, start.elapsed(), Err(message.into()))
+    }
+    
+    /// Prometheus文本格式的指标
+    pub fn render_metrics(&self) -> String {
+        let last_success: Vec<_> = self.sql_files().iter()
+            .filter_map(|sql_file| {
+                let path = self.relative_path(sql_file);
+                let state = self.state_store.get(&path)?;
+                Some((path, state.generated_at))
+            })
+            .collect();
+        self.metrics.render(&last_success, self.sql_executor.pools().connections_in_use(), Utc::now())
+    }
+    
+    /// 处理单个SQL文件并记录执行情况，`force`时不检查更新间隔和暂停状态
+    pub fn run_file(&self, sql_file: &Path, force: bool) -> FileReport {
+        let started_at = Utc::now();
+        let start = Instant::now();
+        let result = self.process_file(sql_file, force);
+        self.report_file(sql_file, force, started_at, start.elapsed(), result)
+    }
+    
+    /// 记录一个文件的处理结果：日志、指标、失败回调和执行记录
+    fn report_file(
+        &self,
+        sql_file: &Path,
+        force: bool,
+        started_at: DateTime<Utc>,
+        duration: Duration,
+        result: Result<RunOutcome, Box<dyn std::error::Error>>
+    ) -> FileReport {
+        let path = self.relative_path(sql_file);
+        let mut report = FileReport {
+            path: path.clone(),
+            status: FileStatus::Generated,
+            reason: None,
+            duration_ms: duration.as_millis() as u64,
+            rows: None,
+            bytes: None,
+            changed: None,
+            output: None,
+            error_class: None,
+            error: None,
+            target: None,
+        };
+        match result {
+            Ok(RunOutcome::Skipped(reason)) => {
+                report.status = FileStatus::Skipped;
+                report.reason = Some(reason.to_string());
+                return report;
+            }
+            Ok(RunOutcome::Generated { rows, bytes, changed, output, target }) => {
+                log::info!(
+                    file = path.as_str(), phase = "done", duration_ms = report.duration_ms, rows, bytes, changed;
+                    "Processed file {} in {}ms ({} rows)", sql_file.display(), report.duration_ms, rows
+                );
+                self.metrics.record_success(&path, duration, rows, bytes, changed);
+                report.rows = Some(rows);
+                report.bytes = Some(bytes);
+                report.changed = Some(changed);
+                report.output = output.map(|output| output.display().to_string());
+                report.target = Some(target);
+            }
+            Err(e) => {
+                let error_class = metrics::error_class(e.as_ref());
+                log::error!(
+                    file = path.as_str(), phase = "done", duration_ms = report.duration_ms, error_class;
+                    "Failed to process file {}: {}", sql_file.display(), e
+                );
+                self.metrics.record_failure(&path, duration, e.as_ref());
+                self.hooks.failed(&FailureEvent {
+                    sql_file,
+                    relative_path: &path,
+                    error_class,
+                    error: e.as_ref(),
+                });
+                report.status = FileStatus::Failed;
+                report.error_class = Some(error_class);
+                report.error = Some(e.to_string());
+            }
+        }
+        
+        self.status.record(&path, RunRecord {
+            started_at,
+            duration_ms: report.duration_ms,
+            rows: report.rows,
+            changed: report.changed,
+            error: report.error.clone(),
+            forced: force,
+            target: report.target.clone(),
+        });
+        report
+    }
+    
+    /// 处理单个SQL文件：检查是否到期、加锁、执行SQL并发布到各输出
+    pub fn process_file(&self, sql_file: &Path, force: bool) -> Result<RunOutcome, Box<dyn std::error::Error>> {
+        let pipeline = self.pipeline();
+        let config = &pipeline.config;
+        let file_handler = &pipeline.file_handler;
+        let state_store = &self.state_store;
+        
+        // 获取相对路径，用于配置文件中的间隔设置
+        let sql_file_str = sql_file.to_string_lossy().to_string();
+        let interval = config.get_interval(&sql_file_str);
+        let file_config = config.get_file_config(&sql_file_str);
+        let sink_names = config.sink_names(Some(&file_config));
+        let json_path = file_handler.sql_to_json_path(sql_file)?;
+        // 状态文件的键和MySQL锁名都使用相对路径，共享状态文件的主机可以使用不同的start_dir
+        let relative_path = self.relative_path(sql_file);
+        let state_key = relative_path.as_str();
+        let sql_content = std::fs::read(sql_file)?;
+        let database = config.database_for(
+            &relative_path,
+            SqlExecutor::directive(&String::from_utf8_lossy(&sql_content), "database"),
+            &file_config
+        )?;
+        
+        // SQL内容或输出配置变化时，不论间隔都需要重新生成
+        let sql_hash = FileHandler::content_hash(&sql_content);
+        let output_root = file_config.output_dir.as_ref().or(config.app.output_dir.as_ref()).unwrap_or(&config.app.start_dir);
+        let output = scanner::relative_path(&json_path, Path::new(output_root));
+        let mut hashed_config = serde_json::json!({ "file": file_config, "output": output, "interval": interval, "sinks": sink_names });
+        if database != DEFAULT_DATABASE {
+            hashed_config["database"] = database.clone().into();
+        }
+        // 脱敏策略变化时也要重新生成，盐只记录摘要
+        if let Some(redaction) = &config.redaction {
+            hashed_config["redaction"] = serde_json::json!({
+                "rules": redaction.rules,
+                "salt": redaction.salt.as_ref().map(|salt| FileHandler::content_hash(salt.expose().as_bytes())),
+            });
+        }
+        // 本地文件的压缩格式或级别变化时重写预压缩文件
+        let uses_file_sink = sink_names.iter().any(|name| name == FILE_SINK);
+        if let Some(compression) = config.compression.as_ref().filter(|_| uses_file_sink) {
+            hashed_config["compression"] = serde_json::to_value(compression)?;
+        }
+        let config_hash = FileHandler::content_hash(hashed_config.to_string().as_bytes());
+        // 不写本地文件时，以状态文件中的生成时间判断间隔
+        let is_due = || -> Result<bool, Box<dyn std::error::Error>> {
+            Ok(force
+                || state_store.is_changed(state_key, &sql_hash, &config_hash)
+                // 上一次有输出失败时，本地文件可能已经更新，不能再以它的修改时间判断
+                || state_store.has_failed_sinks(state_key)
+                || if uses_file_sink {
+                    file_handler.should_update(sql_file, interval)?
+                } else {
+                    state_store.is_expired(state_key, interval)
+                })
+        };
+        
+        // 检查是否需要更新
+        if !force && self.status.is_paused(&relative_path) {
+            log::debug!("Skipping file {} (paused)", sql_file.display());
+            self.load_cached(config, sql_file, &json_path)?;
+            return Ok(RunOutcome::Skipped("paused"));
+        }
+        if !is_due()? {
+            log::debug!("Skipping file {} (not due for update)", sql_file.display());
+            self.load_cached(config, sql_file, &json_path)?;
+            return Ok(RunOutcome::Skipped("not due"));
+        }
+        
+        // 获取锁，防止多个运行实例同时处理同一个文件
+        let Some(_lock) = lock::acquire(config, sql_file, state_key, file_handler, &self.sql_executor)? else {
+            log::info!("Skipping file {} (locked by another runner)", sql_file.display());
+            self.load_cached(config, sql_file, &json_path)?;
+            return Ok(RunOutcome::Skipped("locked"));
+        };
+        
+        // 获取锁之前其他实例可能刚刚完成了更新，重新读取共享的状态文件
+        state_store.refresh(state_key);
+        if !is_due()? {
+            log::debug!("Skipping file {} (updated by another runner)", sql_file.display());
+            self.load_cached(config, sql_file, &json_path)?;
+            return Ok(RunOutcome::Skipped("updated by another runner"));
+        }
+        
+        // 执行SQL文件
+        let max_lag_seconds = file_config.max_lag_seconds;
+        let (results, target) = self.sql_executor.execute_file(sql_file, &database, max_lag_seconds)?;
+        
+        // 生成JSON结果，逐行执行转换和脱敏
+        let no_transform = Transform::default();
+        let transform = pipeline.transforms.get(&sql_file_str).unwrap_or(&no_transform);
+        let json_value = match self.json_generator.generate_value(&results, sql_file, transform, &pipeline.redactor) {
+            Ok(json_value) => json_value,
+            Err(e) => {
+                log_error!(sql_file.display(), "Row Transform", &e.to_string());
+                return Err(Box::new(e));
+            }
+        };
+        
+        // 发布保护：不通过时保留旧文件；上一次的行数记录在状态文件中，不写本地文件时也能检查降幅
+        let root_name = JsonGenerator::root_name(sql_file);
+        if let Some(guard) = &file_config.guard
+            && let Err(e) = guard::check_publish(guard, root_name, &json_value, state_store.get(state_key)
+                .and_then(|state| state.row_count)
+                .or_else(|| guard::previous_row_count(&json_path, root_name))) {
+            log_error!(sql_file.display(), "Publish Guard", &e.reason);
+            return Err(Box::new(e));
+        }
+        
+        // 发送到各个输出
+        let json_str = self.json_generator.render(&json_value)?;
+        let content_hash = FileHandler::content_hash(json_str.as_bytes());
+        let changed = state_store.get(state_key)
+            .and_then(|state| state.content_hash)
+            .is_none_or(|previous| previous != content_hash);
+        let url_path = self.url_path(sql_file);
+        let bytes = json_str.len();
+        let generated_at = Utc::now();
+        let row_count = json_value.get(root_name).and_then(|v| v.as_array()).map_or(0, |a| a.len());
+        let published = self.publish(&pipeline, &sink_names, &PublishedPayload {
+            sql_file,
+            url_path: &url_path,
+            json_path: &json_path,
+            content: &json_str,
+            hash: &content_hash,
+            row_count,
+            changed,
+            generated_at,
+            relative_path: &relative_path,
+        });
+        let failed_sinks = match published {
+            Ok(failed_sinks) => failed_sinks,
+            Err(e) => {
+                if let Some(sink_error) = e.downcast_ref::<SinkError>() {
+                    let failed_sinks = sink_error.failures.iter().map(|(name, _)| name.clone()).collect();
+                    state_store.record_failed_sinks(state_key, failed_sinks)?;
+                }
+                return Err(e);
+            }
+        };
+        
+        self.hooks.published(&PublishEvent {
+            sql_file,
+            relative_path: &relative_path,
+            url_path: &url_path,
+            json: &json_value,
+            content: &json_str,
+            rows: row_count,
+            changed,
+            target: &target,
+        });
+        
+        if let Some(payload_store) = &self.payload_store {
+            // 只有本地文件输出成功时，预压缩文件才刚刚写入或确认与内容一致
+            let precompressed = if uses_file_sink { FileHandler::read_compressed(&json_path) } else { Precompressed::default() };
+            payload_store.publish(url_path, json_str.into_bytes(), precompressed, file_config.cache_control.clone());
+        }
+        
+        if failed_sinks.is_empty() {
+            state_store.record(state_key, FileState {
+                sql_hash,
+                config_hash,
+                content_hash: Some(content_hash),
+                generated_at,
+                row_count: Some(row_count),
+                target: Some(target.clone()),
+                failed_sinks: Vec::new(),
+            })?;
+        } else {
+            // best_effort下部分输出失败：保留上一次的记录，下一次运行仍视为内容变化并重新发布
+            state_store.record_failed_sinks(state_key, failed_sinks)?;
+        }
+        Ok(RunOutcome::Generated { rows: row_count, bytes, changed, output: uses_file_sink.then_some(json_path), target })
+    }
+    
+    /// 依次发送到各个输出，分别记录每个输出的错误
+    ///
+    /// `all`策略下任一输出失败即失败；`best_effort`策略下本地文件写入成功
+    /// （或未使用本地文件时至少一个输出成功）即可，返回被容忍的失败输出名称
+    fn publish(&self, pipeline: &Pipeline, sink_names: &[String], payload: &PublishedPayload) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let mut failures = Vec::new();
+        for name in sink_names {
+            let result = match pipeline.sinks.get(name) {
+                Some(sink) => sink.publish(payload),
+                None => Err(format!("Unknown output sink '{}'", name).into()),
+            };
+            if let Err(e) = result {
+                log_error!(payload.sql_file.display(), format!("Output Sink '{}'", name), &e.to_string());
+                failures.push((name.clone(), e.to_string()));
+            }
+        }
+        
+        let failed = match pipeline.config.app.sink_policy {
+            SinkPolicy::All => !failures.is_empty(),
+            SinkPolicy::BestEffort => failures.len() == sink_names.len()
+                || failures.iter().any(|(name, _)| name == FILE_SINK),
+        };
+        if failed {
+            return Err(Box::new(SinkError { failures }));
+        }
+        Ok(failures.into_iter().map(|(name, _)| name).collect())
+    }
+    
+    /// 未重新生成的文件，首次运行时从磁盘加载到内存
+    fn load_cached(&self, config: &Config, sql_file: &Path, json_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(payload_store) = &self.payload_store {
+            let url_path = self.url_path(sql_file);
+            if !payload_store.contains(&url_path) {
+                let file_config = config.get_file_config(&sql_file.to_string_lossy());
+                let uses_file_sink = config.sink_names(Some(&file_config)).iter().any(|name| name == FILE_SINK);
+                payload_store.load_file(url_path, json_path, file_config.cache_control, uses_file_sink)?;
+            }
+        }
+        Ok(())
+    }
+    
+    /// SQL文件相对于start_dir的路径，扩展名换成`.json`，如`/subdir/kpi.json`
+    pub fn url_path(&self, sql_file: &Path) -> String {
+        PayloadStore::url_path(sql_file, Path::new(&self.config().app.start_dir))
+    }
+    
+    /// SQL文件相对于start_dir的路径，如`subdir/kpi.sql`
+    pub fn relative_path(&self, sql_file: &Path) -> String {
+        scanner::relative_path(sql_file, Path::new(&self.config().app.start_dir))
+    }
+}
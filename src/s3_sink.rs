@@ -0,0 +1,132 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::time::Duration;
+
+use crate::config::S3Config;
+use crate::file_handler::FileHandler;
+use crate::sink::{expand_template, OutputSink, PublishedPayload};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// 上传到S3兼容的对象存储（AWS S3、MinIO等），使用路径风格的URL和SigV4签名
+pub struct S3Sink {
+    agent: ureq::Agent,
+    config: S3Config,
+}
+
+impl S3Sink {
+    pub fn new(config: &S3Config) -> Self {
+        S3Sink {
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(config.timeout_seconds))
+                .build(),
+            config: config.clone(),
+        }
+    }
+    
+    /// 对象键，支持`{path}`和`{stem}`占位符
+    pub fn key_for(&self, url_path: &str) -> String {
+        expand_template(&self.config.key_template, url_path)
+    }
+}
+
+impl OutputSink for S3Sink {
+    fn publish(&self, payload: &PublishedPayload) -> Result<(), Box<dyn std::error::Error>> {
+        let endpoint = self.config.endpoint.trim_end_matches('/');
+        let host = endpoint.split("://").nth(1).unwrap_or(endpoint);
+        let canonical_uri = format!("/{}/{}", uri_encode(&self.config.bucket), uri_encode(&self.key_for(payload.url_path)));
+        
+        let payload_hash = FileHandler::content_hash(payload.content.as_bytes());
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let mut headers = vec![
+            ("content-type".to_string(), "application/json".to_string()),
+            ("host".to_string(), host.to_string()),
+            ("x-amz-content-sha256".to_string(), payload_hash.clone()),
+            ("x-amz-date".to_string(), amz_date.clone()),
+        ];
+        if let Some(cache_control) = &self.config.cache_control {
+            headers.push(("cache-control".to_string(), cache_control.clone()));
+        }
+        let authorization = sign_v4(
+            "PUT", &canonical_uri, &mut headers, &payload_hash, now,
+            &self.config.region, &self.config.access_key, self.config.secret_key.expose()
+        );
+        
+        let mut request = self.agent.put(&format!("{}{}", endpoint, canonical_uri))
+            .set("Authorization", &authorization);
+        for (name, value) in headers.iter().filter(|(name, _)| name != "host") {
+            request = request.set(name, value);
+        }
+        request.send_string(payload.content)?;
+        Ok(())
+    }
+}
+
+/// 计算AWS SigV4签名，返回Authorization头
+///
+/// `headers`需要包含host、x-amz-date和x-amz-content-sha256，会按名称排序
+#[allow(clippy::too_many_arguments)]
+pub fn sign_v4(
+    method: &str,
+    canonical_uri: &str,
+    headers: &mut [(String, String)],
+    payload_hash: &str,
+    time: DateTime<Utc>,
+    region: &str,
+    access_key: &str,
+    secret_key: &str
+) -> String {
+    headers.sort_by(|a, b| a.0.cmp(&b.0));
+    let canonical_headers: String = headers.iter()
+        .map(|(name, value)| format!("{}:{}\n", name.to_lowercase(), value.trim()))
+        .collect();
+    let signed_headers = headers.iter()
+        .map(|(name, _)| name.to_lowercase())
+        .collect::<Vec<_>>()
+        .join(";");
+    
+    let canonical_request = format!(
+        "{}\n{}\n\n{}\n{}\n{}",
+        method, canonical_uri, canonical_headers, signed_headers, payload_hash
+    );
+    
+    let date = time.format("%Y%m%d").to_string();
+    let scope = format!("{}/{}/s3/aws4_request", date, region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        time.format("%Y%m%dT%H%M%SZ"), scope, FileHandler::content_hash(canonical_request.as_bytes())
+    );
+    
+    let mut key = hmac(format!("AWS4{}", secret_key).as_bytes(), date.as_bytes());
+    for part in [region, "s3", "aws4_request"] {
+        key = hmac(&key, part.as_bytes());
+    }
+    let signature = hmac(&key, string_to_sign.as_bytes()).iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{},SignedHeaders={},Signature={}",
+        access_key, scope, signed_headers, signature
+    )
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// 按SigV4规则编码路径，保留`/`和非保留字符
+fn uri_encode(path: &str) -> String {
+    let mut encoded = String::new();
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
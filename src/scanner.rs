@@ -13,7 +13,21 @@ pub fn scan_sql_files<P: AsRef<Path>>(start_dir: P) -> Vec<std::path::PathBuf> {
     sql_files
 }
 
+/// 相对于`root`的路径，以`/`分隔，如`subdir/kpi.sql`；不在`root`下时使用整个路径中的普通部分
+///
+/// 用作状态文件的键和MySQL锁名，各主机上`start_dir`的写法不同时结果相同
+pub fn relative_path(path: &Path, root: &Path) -> String {
+    path.strip_prefix(root).unwrap_or(path)
+        .components()
+        .filter_map(|component| match component {
+            std::path::Component::Normal(part) => Some(part.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
 fn is_sql_file(entry: &DirEntry) -> bool {
     entry.file_type().is_file() && 
-    entry.path().extension().map_or(false, |ext| ext == "sql")
+    entry.path().extension().is_some_and(|ext| ext == "sql")
 }
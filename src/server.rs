@@ -0,0 +1,261 @@
+use axum::Router;
+use axum::body::{Body, Bytes};
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap, Method, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::routing::get;
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures_util::Stream;
+use serde::Deserialize;
+use std::convert::Infallible;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::admin;
+use crate::cache::{ChangeEvent, PayloadStore};
+use crate::file_handler::FileHandler;
+use crate::query::PayloadQuery;
+use crate::runner::Runner;
+
+#[derive(Clone)]
+struct ServerState {
+    runner: Arc<Runner>,
+    payload_store: Arc<PayloadStore>,
+    cache_control: String,
+}
+
+/// serve模式：定时刷新SQL结果，并从内存中提供JSON
+///
+/// `config_path`用于管理接口重新加载配置
+pub async fn serve(runner: Arc<Runner>, payload_store: Arc<PayloadStore>, listen: Option<String>, config_path: PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+    let server_config = runner.config().server.clone().unwrap_or_default();
+    let listen = listen.unwrap_or(server_config.listen);
+    
+    // 定时刷新，各文件是否需要重新生成仍由更新间隔和状态文件决定；间隔每次从当前配置读取
+    let scheduler = runner.clone();
+    tokio::spawn(async move {
+        loop {
+            scheduler.run_once().await;
+            let refresh = scheduler.config().server.as_ref().map_or(60, |server| server.refresh_seconds);
+            tokio::time::sleep(Duration::from_secs(refresh)).await;
+        }
+    });
+    
+    let mut app = Router::new()
+        .route("/_events", get(events))
+        .route("/metrics", get(metrics))
+        .fallback(serve_payload)
+        .with_state(ServerState {
+            runner: runner.clone(),
+            payload_store,
+            cache_control: server_config.cache_control,
+        });
+    if runner.config().admin.is_some() {
+        app = app.nest("/_admin", admin::router(runner.clone(), config_path)?);
+        log::info!("Admin API enabled under /_admin");
+    }
+    
+    let listener = tokio::net::TcpListener::bind(&listen).await?;
+    log::info!("Serving JSON on http://{}", listener.local_addr()?);
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn serve_payload(State(state): State<ServerState>, method: Method, uri: Uri, headers: HeaderMap) -> Response {
+    if method != Method::GET && method != Method::HEAD {
+        return StatusCode::METHOD_NOT_ALLOWED.into_response();
+    }
+    
+    // 文件名可以包含空格和中文等字符，客户端按百分号编码发送
+    let Some(payload) = decode_path(uri.path()).and_then(|path| state.payload_store.get(&path)) else {
+        return json_error(StatusCode::NOT_FOUND, "not found");
+    };
+    
+    // 带查询参数时对解析后的结果执行查询，不使用预压缩的内容
+    let params = Query::<Vec<(String, String)>>::try_from_uri(&uri).map(|query| query.0).unwrap_or_default();
+    let query = match PayloadQuery::parse(&params) {
+        Ok(query) => query,
+        Err(e) => return json_error(StatusCode::BAD_REQUEST, &e),
+    };
+    // 只有缓存参数等与列无关的参数时返回原始内容
+    let query = query.and_then(|mut query| {
+        if let Some(parsed) = payload.parsed() {
+            query.ignore_unknown_filters(parsed);
+        }
+        (!query.is_empty()).then_some(query)
+    });
+    
+    let (content, encoding, etag, total) = if let Some(query) = query {
+        let Some(parsed) = payload.parsed() else {
+            return json_error(StatusCode::UNPROCESSABLE_ENTITY, "payload is not valid JSON");
+        };
+        let (result, total) = match query.apply(parsed) {
+            Ok(result) => result,
+            Err(e) => return json_error(StatusCode::BAD_REQUEST, &e),
+        };
+        let body = match serde_json::to_vec_pretty(&result) {
+            Ok(body) => body,
+            Err(e) => return json_error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+        };
+        let etag = format!("\"{}\"", FileHandler::content_hash(&body));
+        (Bytes::from(body), None, etag, Some(total))
+    } else {
+        // 优先使用预压缩的内容，不同编码使用不同的强ETag
+        let accept_encoding = headers.get(header::ACCEPT_ENCODING)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("");
+        let (content, encoding) = match (&payload.brotli, &payload.gzip) {
+            (Some(br), _) if accepts_encoding(accept_encoding, "br") => (br, Some("br")),
+            (_, Some(gz)) if accepts_encoding(accept_encoding, "gzip") => (gz, Some("gzip")),
+            _ => (&payload.body, None),
+        };
+        let etag = match encoding {
+            Some(encoding) => format!("{}-{}\"", payload.etag.trim_end_matches('"'), encoding),
+            None => payload.etag.clone(),
+        };
+        (content.clone(), encoding, etag, None)
+    };
+    
+    let cache_control = payload.cache_control.as_deref().unwrap_or(&state.cache_control);
+    let mut response = Response::builder()
+        .header(header::ETAG, &etag)
+        .header(header::VARY, "Accept-Encoding")
+        .header(header::LAST_MODIFIED, http_date(payload.last_modified))
+        .header(header::CACHE_CONTROL, cache_control);
+    // 过滤后、分页前的行数
+    if let Some(total) = total {
+        response = response.header("X-Total-Count", total);
+    }
+    
+    // If-None-Match优先于If-Modified-Since
+    let not_modified = match headers.get(header::IF_NONE_MATCH) {
+        Some(value) => etag_matches(value.to_str().unwrap_or(""), &etag),
+        None => headers.get(header::IF_MODIFIED_SINCE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| DateTime::parse_from_rfc2822(value).ok())
+            .is_some_and(|since| payload.last_modified.timestamp() <= since.timestamp()),
+    };
+    
+    let response = if not_modified {
+        response.status(StatusCode::NOT_MODIFIED).body(Body::empty())
+    } else {
+        let body = if method == Method::HEAD { Body::empty() } else { Body::from(content.clone()) };
+        let response = response
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::CONTENT_LENGTH, content.len());
+        match encoding {
+            Some(encoding) => response.header(header::CONTENT_ENCODING, encoding),
+            None => response,
+        }.body(body)
+    };
+    response.unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
+
+/// Prometheus指标
+async fn metrics(State(state): State<ServerState>) -> Response {
+    let runner = state.runner.clone();
+    match tokio::task::spawn_blocking(move || runner.render_metrics()).await {
+        Ok(metrics) => ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], metrics).into_response(),
+        Err(e) => json_error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    }
+}
+
+#[derive(Deserialize)]
+struct EventsQuery {
+    /// 逗号分隔的URL路径，不设置时订阅所有文件
+    paths: Option<String>,
+    /// 事件中是否带上完整的JSON内容
+    #[serde(default)]
+    full: bool,
+}
+
+/// Server-Sent Events：内容变化时推送`change`事件，接收太慢丢失事件时推送`lagged`事件
+async fn events(State(state): State<ServerState>, Query(query): Query<EventsQuery>) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let paths: Vec<String> = query.paths.iter()
+        .flat_map(|paths| paths.split(','))
+        .map(|path| path.trim().to_string())
+        .filter(|path| !path.is_empty())
+        .collect();
+    let receiver = state.payload_store.subscribe();
+    
+    let stream = futures_util::stream::unfold((receiver, paths, query.full), |(mut receiver, paths, full)| async move {
+        loop {
+            let event = match receiver.recv().await {
+                Ok(change) if paths.is_empty() || paths.contains(&change.url_path) => Event::default()
+                    .event("change")
+                    .id(change.payload.etag.trim_matches('"'))
+                    .data(change_event_data(&change, full)),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => {
+                    log::warn!("Event subscriber lagged, {} events dropped", missed);
+                    Event::default().event("lagged").data(serde_json::json!({ "missed": missed }).to_string())
+                }
+                Err(RecvError::Closed) => return None,
+            };
+            return Some((Ok(event), (receiver, paths, full)));
+        }
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// `change`事件的内容：路径、新内容的SHA-256和修改时间，`full`时带上完整的JSON
+pub fn change_event_data(change: &ChangeEvent, full: bool) -> String {
+    let mut data = serde_json::json!({
+        "path": change.url_path,
+        "sha256": change.payload.etag.trim_matches('"'),
+        "last_modified": change.payload.last_modified.to_rfc3339_opts(SecondsFormat::Secs, true),
+    });
+    if full {
+        data["payload"] = serde_json::from_slice(&change.payload.body).unwrap_or(serde_json::Value::Null);
+    }
+    data.to_string()
+}
+
+fn json_error(status: StatusCode, message: &str) -> Response {
+    (status, axum::Json(serde_json::json!({ "error": message }))).into_response()
+}
+
+/// If-None-Match可以是`*`或逗号分隔的ETag列表，弱比较忽略`W/`前缀
+pub fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match.split(',')
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// Accept-Encoding中是否接受该编码（`q=0`表示不接受）
+pub fn accepts_encoding(accept_encoding: &str, encoding: &str) -> bool {
+    accept_encoding.split(',').any(|item| {
+        let mut parts = item.split(';').map(|part| part.trim());
+        let name = parts.next().unwrap_or("");
+        let q = parts.find_map(|param| param.strip_prefix("q="))
+            .and_then(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+        (name.eq_ignore_ascii_case(encoding) || name == "*") && q > 0.0
+    })
+}
+
+/// 解码路径中的百分号编码（`%20`、`%E4%B8%AD`等），结果不是有效的UTF-8时返回None
+pub fn decode_path(path: &str) -> Option<String> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// HTTP日期格式，如`Sun, 06 Nov 1994 08:49:37 GMT`
+pub fn http_date(time: DateTime<Utc>) -> String {
+    time.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
@@ -0,0 +1,220 @@
+use chrono::{DateTime, Utc};
+use mysql::prelude::Queryable;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::{Config, MysqlSinkConfig, SinkConfig, WebhookConfig};
+use crate::db::DbPool;
+use crate::file_handler::FileHandler;
+use crate::redis_sink::RedisSink;
+use crate::s3_sink::S3Sink;
+
+/// 内置的本地文件输出名称
+pub const FILE_SINK: &str = "file";
+
+/// 一次生成的结果，发送给各个输出
+pub struct PublishedPayload<'a> {
+    pub sql_file: &'a Path,
+    /// SQL文件相对于start_dir的路径，如`/subdir/kpi.json`
+    pub url_path: &'a str,
+    /// 本地JSON文件路径
+    pub json_path: &'a Path,
+    pub content: &'a str,
+    /// 内容的SHA-256
+    pub hash: &'a str,
+    pub row_count: usize,
+    /// 与上一次发布的内容相比是否有变化
+    pub changed: bool,
+    pub generated_at: DateTime<Utc>,
+    /// SQL文件相对于start_dir的路径，如`subdir/kpi.sql`
+    pub relative_path: &'a str,
+}
+
+/// JSON结果的输出目标
+pub trait OutputSink: Send + Sync {
+    fn publish(&self, payload: &PublishedPayload) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+/// 一个或多个输出失败，按输出名称分别记录
+#[derive(Debug)]
+pub struct SinkError {
+    pub failures: Vec<(String, String)>,
+}
+
+impl fmt::Display for SinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failures = self.failures.iter()
+            .map(|(name, error)| format!("sink '{}': {}", name, error))
+            .collect::<Vec<_>>();
+        write!(f, "{}", failures.join("; "))
+    }
+}
+
+impl std::error::Error for SinkError {}
+
+/// 展开键名或URL模板中的`{path}`（`subdir/kpi.json`）和`{stem}`（`kpi`）占位符
+pub fn expand_template(template: &str, url_path: &str) -> String {
+    let path = url_path.trim_start_matches('/');
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let stem = name.strip_suffix(".json").unwrap_or(name);
+    template.replace("{path}", path).replace("{stem}", stem)
+}
+
+/// 按名称创建配置中的所有输出，`file`始终可用
+pub fn build_sinks(
+    config: &Config,
+    file_handler: Arc<FileHandler>,
+    pool: Option<&DbPool>
+) -> Result<HashMap<String, Arc<dyn OutputSink>>, Box<dyn std::error::Error>> {
+    let mut sinks: HashMap<String, Arc<dyn OutputSink>> = HashMap::new();
+    sinks.insert(FILE_SINK.to_string(), Arc::new(FileSink { file_handler }));
+    
+    // [redis]是名为redis的输出的简写
+    if let Some(redis) = &config.redis {
+        sinks.insert("redis".to_string(), Arc::new(RedisSink::new(redis)?));
+    }
+    
+    for (name, sink_config) in config.sinks.iter().flatten() {
+        let sink: Arc<dyn OutputSink> = match sink_config {
+            SinkConfig::Redis(redis) => Arc::new(RedisSink::new(redis)?),
+            SinkConfig::S3(s3) => Arc::new(S3Sink::new(s3)),
+            SinkConfig::Webhook(webhook) => Arc::new(WebhookSink::new(webhook)),
+            SinkConfig::Mysql(mysql) => {
+                let pool = pool.ok_or_else(|| format!("output sink '{}' requires a MySQL [database]", name))?;
+                Arc::new(MysqlTableSink::new(mysql, pool.clone())?)
+            }
+        };
+        sinks.insert(name.clone(), sink);
+    }
+    
+    // 提前检查引用的输出名称
+    for name in config.sink_names(None).iter()
+        .chain(config.files.iter().flatten().flat_map(|(_, f)| f.sinks.iter().flatten())) {
+        if !sinks.contains_key(name) {
+            return Err(format!("Unknown output sink '{}'", name).into());
+        }
+    }
+    
+    Ok(sinks)
+}
+
+/// 原子写入本地JSON文件
+pub struct FileSink {
+    file_handler: Arc<FileHandler>,
+}
+
+impl OutputSink for FileSink {
+    fn publish(&self, payload: &PublishedPayload) -> Result<(), Box<dyn std::error::Error>> {
+        if self.file_handler.write_json_if_changed(payload.json_path, payload.content)? {
+            log::info!("Generated JSON file: {}", payload.json_path.display());
+        } else {
+            log::info!("JSON file unchanged, skipped write: {}", payload.json_path.display());
+        }
+        Ok(())
+    }
+}
+
+/// 以HTTP PUT/POST把JSON发送到指定URL
+pub struct WebhookSink {
+    agent: ureq::Agent,
+    config: WebhookConfig,
+}
+
+impl WebhookSink {
+    pub fn new(config: &WebhookConfig) -> Self {
+        WebhookSink {
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(config.timeout_seconds))
+                .build(),
+            config: config.clone(),
+        }
+    }
+    
+    /// URL中支持`{path}`和`{stem}`占位符
+    pub fn url_for(&self, url_path: &str) -> String {
+        expand_template(self.config.url.expose(), url_path)
+    }
+}
+
+impl OutputSink for WebhookSink {
+    fn publish(&self, payload: &PublishedPayload) -> Result<(), Box<dyn std::error::Error>> {
+        if self.config.only_changed && !payload.changed {
+            return Ok(());
+        }
+        
+        let mut request = self.agent.request(&self.config.method.to_uppercase(), &self.url_for(payload.url_path))
+            .set("Content-Type", "application/json")
+            .set("X-Content-SHA256", payload.hash)
+            .set("X-FastSQL2Json-Path", payload.url_path)
+            .set("X-FastSQL2Json-Rows", &payload.row_count.to_string());
+        for (name, value) in &self.config.headers {
+            request = request.set(name, value.expose());
+        }
+        request.send_string(payload.content)?;
+        Ok(())
+    }
+}
+
+/// 以upsert方式写入MySQL缓存表，供只能访问数据库的服务读取
+///
+/// 以SQL文件相对于start_dir的路径为主键，在单独的读写事务中写入
+pub struct MysqlTableSink {
+    pool: DbPool,
+    table: String,
+}
+
+impl MysqlTableSink {
+    pub fn new(config: &MysqlSinkConfig, pool: DbPool) -> Result<Self, Box<dyn std::error::Error>> {
+        let sink = MysqlTableSink {
+            pool,
+            table: DbPool::quote_identifier(&config.table)?,
+        };
+        if config.create_table {
+            let query = Self::create_table_sql(&sink.table);
+            sink.pool.write_transaction(|tx| Ok(tx.query_drop(&query)?))?;
+        }
+        Ok(sink)
+    }
+    
+    /// `table`为已经加引号的表名
+    pub fn create_table_sql(table: &str) -> String {
+        format!(
+            "CREATE TABLE IF NOT EXISTS {} (\
+             path VARCHAR(512) NOT NULL PRIMARY KEY, \
+             payload JSON NOT NULL, \
+             content_hash CHAR(64) NOT NULL, \
+             generated_at DATETIME(6) NOT NULL, \
+             row_count INT UNSIGNED NOT NULL)",
+            table
+        )
+    }
+    
+    pub fn upsert_sql(table: &str) -> String {
+        format!(
+            "INSERT INTO {} (path, payload, content_hash, generated_at, row_count) VALUES (?, ?, ?, ?, ?) \
+             ON DUPLICATE KEY UPDATE payload = VALUES(payload), content_hash = VALUES(content_hash), \
+             generated_at = VALUES(generated_at), row_count = VALUES(row_count)",
+            table
+        )
+    }
+}
+
+impl OutputSink for MysqlTableSink {
+    fn publish(&self, payload: &PublishedPayload) -> Result<(), Box<dyn std::error::Error>> {
+        let query = Self::upsert_sql(&self.table);
+        let generated_at = payload.generated_at.format("%Y-%m-%d %H:%M:%S%.6f").to_string();
+        self.pool.write_transaction(|tx| {
+            tx.exec_drop(&query, (
+                payload.relative_path,
+                payload.content,
+                payload.hash,
+                &generated_at,
+                payload.row_count as u64,
+            ))?;
+            Ok(())
+        })
+    }
+}
@@ -1,34 +1,74 @@
-use crate::db::DbPool;
+use crate::backend::ResultSet;
+use crate::db::{DbPool, DbPools, QueryTarget};
 use crate::logger::log_error;
 use log::{info, error};
-use mysql::*;
 use std::fs;
 use std::path::Path;
+use std::time::Instant;
 
 pub struct SqlExecutor {
-    pool: DbPool,
+    pools: DbPools,
 }
 
 impl SqlExecutor {
-    pub fn new(pool: DbPool) -> Self {
+    pub fn new(pools: DbPools) -> Self {
         SqlExecutor {
-            pool,
+            pools,
         }
     }
     
-    pub fn execute_file<P: AsRef<Path>>(&self, file_path: P) -> Result<Vec<Vec<Row>>, Box<dyn std::error::Error>> {
+    /// `[database]`为MySQL时的连接池
+    pub fn mysql_pool(&self) -> Option<&DbPool> {
+        self.pools.mysql_pool()
+    }
+    
+    pub fn pools(&self) -> &DbPools {
+        &self.pools
+    }
+    
+    /// 在名为`database`的连接上执行SQL文件，返回结果和实际执行的服务器
+    pub fn execute_file<P: AsRef<Path>>(
+        &self,
+        file_path: P,
+        database: &str,
+        max_lag_seconds: Option<f64>
+    ) -> Result<(Vec<ResultSet>, QueryTarget), Box<dyn std::error::Error>> {
         let file_path = file_path.as_ref();
         let sql_content = fs::read_to_string(file_path)?;
-        let cleaned_sql = Self::clean_sql(&sql_content);
+        self.execute_sql(&file_path.display().to_string(), &sql_content, database, max_lag_seconds)
+    }
+    
+    /// 执行SQL文本，`label`是日志中的文件名
+    pub fn execute_sql(
+        &self,
+        label: &str,
+        sql: &str,
+        database: &str,
+        max_lag_seconds: Option<f64>
+    ) -> Result<(Vec<ResultSet>, QueryTarget), Box<dyn std::error::Error>> {
+        let cleaned_sql = Self::clean_sql(sql);
         
-        match self.pool.execute_query(&cleaned_sql) {
+        let (pool, target) = match self.pools.route(database, max_lag_seconds) {
+            Ok(route) => route,
+            Err(e) => {
+                log_error!(label, "Replica Routing", &e.to_string());
+                return Err(e);
+            }
+        };
+        
+        let start = Instant::now();
+        match pool.query(&cleaned_sql) {
             Ok(results) => {
-                info!("Successfully executed file: {}", file_path.display());
-                Ok(results)
+                let duration_ms = start.elapsed().as_millis() as u64;
+                info!(
+                    file = label, phase = "execute", database, server = target.server.as_str(), duration_ms;
+                    "Successfully executed file: {}", label
+                );
+                Ok((results, target))
             },
             Err(e) => {
-                log_error!(file_path.display(), "SQL Execution Error", &e.to_string());
-                Err(e.into())
+                log_error!(label, "SQL Execution Error", &e.to_string());
+                Err(e)
             }
         }
     }
@@ -48,7 +88,7 @@ impl SqlExecutor {
                 in_multiline_comment = true;
                 chars.next();
             } else if c == '-' && chars.peek() == Some(&'-') {
-                while let Some(next_c) = chars.next() {
+                for next_c in chars.by_ref() {
                     if next_c == '\n' {
                         cleaned.push(next_c);
                         break;
@@ -62,5 +102,20 @@ impl SqlExecutor {
         cleaned
     }
     
+    /// SQL文件开头注释中的指令，如`-- @database: reporting`中`database`的值
+    pub fn directive<'a>(sql: &'a str, name: &str) -> Option<&'a str> {
+        for line in sql.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let comment = line.strip_prefix("--")?;
+            let value = comment.trim_start()
+                .strip_prefix('@')
+                .and_then(|directive| directive.strip_prefix(name))
+                .and_then(|rest| rest.trim_start().strip_prefix(':'));
+            if let Some(value) = value {
+                return Some(value.trim());
+            }
+        }
+        None
+    }
+    
 
 }
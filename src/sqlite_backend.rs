@@ -0,0 +1,82 @@
+use rusqlite::types::ValueRef;
+use rusqlite::{Batch, Connection, OpenFlags};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::backend::{Backend, ResultSet, SqlValue};
+use crate::config::DatabaseConfig;
+
+/// SQLite数据库，`database`为数据库文件路径，所有查询共用一个连接
+///
+/// `read_only`时以只读方式打开并开启`PRAGMA query_only`，SQL文件无法修改数据
+pub struct SqliteBackend {
+    conn: Mutex<Connection>,
+    read_only: bool,
+}
+
+impl SqliteBackend {
+    pub fn open(config: &DatabaseConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let conn = if config.read_only && config.database != ":memory:" {
+            Connection::open_with_flags(
+                &config.database,
+                OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX
+            )?
+        } else {
+            Connection::open(&config.database)?
+        };
+        conn.busy_timeout(Duration::from_secs(10))?;
+        if config.read_only {
+            conn.pragma_update(None, "query_only", true)?;
+        }
+        Ok(SqliteBackend {
+            conn: Mutex::new(conn),
+            read_only: config.read_only,
+        })
+    }
+}
+
+impl Backend for SqliteBackend {
+    /// 依次执行SQL中的每条语句，有结果列的语句各自返回一个结果集
+    fn query(&self, sql: &str) -> Result<Vec<ResultSet>, Box<dyn std::error::Error>> {
+        let conn = self.conn.lock().unwrap();
+        let mut results = Vec::new();
+        for statement in Batch::new(&conn, sql) {
+            let mut statement = statement?;
+            if statement.column_count() == 0 {
+                statement.raw_execute()?;
+                continue;
+            }
+            
+            let columns: Vec<String> = statement.column_names().into_iter().map(String::from).collect();
+            let mut rows = Vec::new();
+            let mut query = statement.raw_query();
+            while let Some(row) = query.next()? {
+                let mut values = Vec::with_capacity(columns.len());
+                for i in 0..columns.len() {
+                    values.push(sqlite_value(row.get_ref(i)?));
+                }
+                rows.push(values);
+            }
+            results.push(ResultSet { columns, rows });
+        }
+        Ok(results)
+    }
+    
+    fn version(&self) -> Result<String, Box<dyn std::error::Error>> {
+        Ok(format!("SQLite {}", rusqlite::version()))
+    }
+    
+    fn read_only(&self) -> bool {
+        self.read_only
+    }
+}
+
+fn sqlite_value(value: ValueRef) -> SqlValue {
+    match value {
+        ValueRef::Null => SqlValue::Null,
+        ValueRef::Integer(i) => SqlValue::Int(i),
+        ValueRef::Real(f) => SqlValue::Float(f),
+        ValueRef::Text(text) => SqlValue::Text(String::from_utf8_lossy(text).into_owned()),
+        ValueRef::Blob(blob) => SqlValue::Bytes(blob.to_vec()),
+    }
+}
@@ -0,0 +1,123 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::db::QueryTarget;
+use crate::file_handler::write_file_atomic;
+
+/// 上一次成功生成时的SQL内容哈希和配置哈希
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FileState {
+    pub sql_hash: String,
+    pub config_hash: String,
+    /// 上一次发布内容的SHA-256
+    #[serde(default)]
+    pub content_hash: Option<String>,
+    pub generated_at: DateTime<Utc>,
+    /// 上一次发布的行数，用于发布保护的降幅检查
+    #[serde(default)]
+    pub row_count: Option<usize>,
+    /// 生成时执行SQL的服务器和复制延迟
+    #[serde(default)]
+    pub target: Option<QueryTarget>,
+    /// 上一次发布失败的输出，下一次运行时不论更新间隔都重新发布
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failed_sinks: Vec<String>,
+}
+
+/// 记录每个SQL文件上一次生成时的指纹，SQL或输出配置变化时强制重新生成
+pub struct StateStore {
+    path: PathBuf,
+    entries: Mutex<HashMap<String, FileState>>,
+}
+
+impl StateStore {
+    /// 状态文件不存在或无法解析时从空状态开始
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let entries = Self::read_entries(&path);
+        
+        StateStore {
+            path,
+            entries: Mutex::new(entries),
+        }
+    }
+    
+    /// 与上一次成功生成时相比，SQL内容或配置是否发生了变化
+    pub fn is_changed(&self, key: &str, sql_hash: &str, config_hash: &str) -> bool {
+        let entries = self.entries.lock().unwrap();
+        match entries.get(key) {
+            Some(state) => state.sql_hash != sql_hash || state.config_hash != config_hash,
+            None => true,
+        }
+    }
+    
+    /// 上一次发布是否有输出失败
+    pub fn has_failed_sinks(&self, key: &str) -> bool {
+        self.entries.lock().unwrap().get(key).is_some_and(|state| !state.failed_sinks.is_empty())
+    }
+    
+    /// 记录发布失败的输出，保留上一次成功生成的其他记录；没有记录时下一次运行本来就会重新生成
+    pub fn record_failed_sinks(&self, key: &str, failed_sinks: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+        match self.get(key) {
+            Some(state) => self.record(key, FileState { failed_sinks, ..state }),
+            None => Ok(()),
+        }
+    }
+    
+    /// 距上一次成功生成是否已超过更新间隔（分钟），用于不写本地文件的输出
+    pub fn is_expired(&self, key: &str, interval: Option<u64>) -> bool {
+        let Some(interval) = interval else {
+            return true;
+        };
+        match self.entries.lock().unwrap().get(key) {
+            Some(state) => (Utc::now() - state.generated_at).num_seconds() > (interval * 60) as i64,
+            None => true,
+        }
+    }
+    
+    /// 从状态文件重新读取一个文件的状态，文件中的记录更新时替换内存中的记录
+    ///
+    /// 共享状态文件时，其他主机在本实例启动之后完成的生成只能这样看到
+    pub fn refresh(&self, key: &str) {
+        let Some(on_disk) = Self::read_entries(&self.path).remove(key) else {
+            return;
+        };
+        let mut entries = self.entries.lock().unwrap();
+        if entries.get(key).is_none_or(|state| state.generated_at < on_disk.generated_at) {
+            entries.insert(key.to_string(), on_disk);
+        }
+    }
+    
+    pub fn get(&self, key: &str) -> Option<FileState> {
+        self.entries.lock().unwrap().get(key).cloned()
+    }
+    
+    /// 记录一次成功的生成并立即保存
+    pub fn record(&self, key: &str, state: FileState) -> Result<(), Box<dyn std::error::Error>> {
+        let mut entries = self.entries.lock().unwrap();
+        // 保留其他主机写入的更新的记录，不用内存中的旧记录覆盖
+        for (other, on_disk) in Self::read_entries(&self.path) {
+            if entries.get(&other).is_none_or(|state| state.generated_at < on_disk.generated_at) {
+                entries.insert(other, on_disk);
+            }
+        }
+        entries.insert(key.to_string(), state);
+        
+        let content = serde_json::to_string_pretty(&*entries)?;
+        write_file_atomic(&self.path, content.as_bytes())
+    }
+    
+    fn read_entries(path: &Path) -> HashMap<String, FileState> {
+        match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                log::warn!("Ignoring unreadable state file {}: {}", path.display(), e);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        }
+    }
+}
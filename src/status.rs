@@ -0,0 +1,88 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::db::QueryTarget;
+
+/// 单次处理的结果
+pub enum RunOutcome {
+    /// 未执行SQL，如未到更新时间、已暂停或被其他实例锁定
+    Skipped(&'static str),
+    /// 执行了SQL并发布了JSON
+    Generated {
+        /// 结果的行数
+        rows: usize,
+        /// JSON的字节数
+        bytes: usize,
+        /// 内容与上一次发布的不同
+        changed: bool,
+        /// 本地JSON文件路径（使用file输出时）
+        output: Option<PathBuf>,
+        /// 执行SQL的服务器
+        target: QueryTarget,
+    },
+}
+
+/// 一次执行SQL的记录
+#[derive(Serialize, Debug, Clone)]
+pub struct RunRecord {
+    /// 开始时间
+    pub started_at: DateTime<Utc>,
+    /// 耗时（毫秒）
+    pub duration_ms: u64,
+    /// 结果的行数，失败时为空
+    pub rows: Option<usize>,
+    /// 内容与上一次发布的不同，失败时为空
+    pub changed: Option<bool>,
+    /// 失败的原因
+    pub error: Option<String>,
+    /// 通过管理接口手动刷新
+    pub forced: bool,
+    /// 执行SQL的服务器
+    pub target: Option<QueryTarget>,
+}
+
+#[derive(Default)]
+struct FileStatus {
+    history: VecDeque<RunRecord>,
+    paused: bool,
+}
+
+/// 按SQL文件相对路径保存最近的执行记录和暂停状态，只保存在内存中
+pub(crate) struct StatusBoard {
+    entries: Mutex<HashMap<String, FileStatus>>,
+    history_size: usize,
+}
+
+impl StatusBoard {
+    pub fn new(history_size: usize) -> Self {
+        StatusBoard {
+            entries: Mutex::new(HashMap::new()),
+            history_size: history_size.max(1),
+        }
+    }
+    
+    pub fn record(&self, path: &str, record: RunRecord) {
+        let mut entries = self.entries.lock().unwrap();
+        let status = entries.entry(path.to_string()).or_default();
+        status.history.push_front(record);
+        status.history.truncate(self.history_size);
+    }
+    
+    /// 最近的执行记录，最新的在前
+    pub fn history(&self, path: &str) -> Vec<RunRecord> {
+        self.entries.lock().unwrap().get(path)
+            .map(|status| status.history.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+    
+    pub fn set_paused(&self, path: &str, paused: bool) {
+        self.entries.lock().unwrap().entry(path.to_string()).or_default().paused = paused;
+    }
+    
+    pub fn is_paused(&self, path: &str) -> bool {
+        self.entries.lock().unwrap().get(path).is_some_and(|status| status.paused)
+    }
+}
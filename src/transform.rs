@@ -0,0 +1,187 @@
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::config::{CastType, RenameCase, TransformConfig};
+use crate::expr::{self, Expr};
+
+/// 行转换失败，旧的JSON保持不变
+#[derive(Debug)]
+pub struct TransformError {
+    pub column: String,
+    pub reason: String,
+}
+
+impl fmt::Display for TransformError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Row transform failed for column '{}': {}", self.column, self.reason)
+    }
+}
+
+impl std::error::Error for TransformError {}
+
+/// 由[`TransformConfig`]编译得到的逐行转换，默认值不做任何转换
+#[derive(Debug, Default)]
+pub struct Transform {
+    computed: Vec<(String, Expr)>,
+    defaults: Vec<(String, Value)>,
+    cast: Vec<(String, CastType)>,
+    keep: Option<HashSet<String>>,
+    drop: HashSet<String>,
+    rename: HashMap<String, String>,
+    rename_case: Option<RenameCase>,
+}
+
+impl Transform {
+    /// 解析计算列的表达式
+    pub fn new(config: &TransformConfig) -> Result<Self, String> {
+        let computed = config.computed.iter()
+            .map(|(name, source)| {
+                expr::parse(source)
+                    .map(|expr| (name.clone(), expr))
+                    .map_err(|e| format!("computed '{}': {}", name, e))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Transform {
+            computed,
+            defaults: config.defaults.iter().map(|(name, value)| (name.clone(), value.clone())).collect(),
+            cast: config.cast.iter().map(|(name, cast)| (name.clone(), *cast)).collect(),
+            keep: config.keep.as_ref().map(|keep| keep.iter().cloned().collect()),
+            drop: config.drop.iter().cloned().collect(),
+            rename: config.rename.clone().into_iter().collect(),
+            rename_case: config.rename_case,
+        })
+    }
+    
+    pub fn apply(&self, mut row: Map<String, Value>) -> Result<Map<String, Value>, TransformError> {
+        // 计算列只能引用SQL结果中的列，互相之间没有先后顺序
+        let computed = self.computed.iter()
+            .map(|(name, expr)| {
+                expr.eval(&row)
+                    .map(|value| (name.clone(), value))
+                    .map_err(|reason| TransformError { column: name.clone(), reason })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        row.extend(computed);
+        
+        for (name, value) in &self.defaults {
+            if row.get(name).is_none_or(Value::is_null) {
+                row.insert(name.clone(), value.clone());
+            }
+        }
+        
+        for (name, cast) in &self.cast {
+            if let Some(value) = row.get_mut(name) {
+                *value = cast_value(value.take(), *cast)
+                    .map_err(|reason| TransformError { column: name.clone(), reason })?;
+            }
+        }
+        
+        if let Some(keep) = &self.keep {
+            row.retain(|name, _| keep.contains(name));
+        }
+        row.retain(|name, _| !self.drop.contains(name));
+        
+        if self.rename.is_empty() && self.rename_case.is_none() {
+            return Ok(row);
+        }
+        let mut renamed = Map::new();
+        let mut sources = HashMap::new();
+        for (name, value) in row {
+            let new_name = match (self.rename.get(&name), self.rename_case) {
+                (Some(new_name), _) => new_name.clone(),
+                (None, Some(case)) => convert_case(&name, case),
+                (None, None) => name.clone(),
+            };
+            if let Some(other) = sources.insert(new_name.clone(), name.clone()) {
+                return Err(TransformError {
+                    column: name,
+                    reason: format!("renamed to '{}', which is also the new name of '{}'", new_name, other),
+                });
+            }
+            renamed.insert(new_name, value);
+        }
+        Ok(renamed)
+    }
+}
+
+fn cast_value(value: Value, cast: CastType) -> Result<Value, String> {
+    let invalid = |value: &Value| format!("cannot cast {} to {}", value, format!("{:?}", cast).to_lowercase());
+    Ok(match (cast, value) {
+        (_, Value::Null) => Value::Null,
+        (CastType::Bool, Value::Bool(b)) => Value::Bool(b),
+        (CastType::Bool, Value::Number(n)) => Value::Bool(n.as_f64().is_some_and(|n| n != 0.0)),
+        (CastType::Bool, Value::String(s)) => match s.trim().to_lowercase().as_str() {
+            "1" | "true" | "yes" | "y" | "on" => Value::Bool(true),
+            "0" | "false" | "no" | "n" | "off" | "" => Value::Bool(false),
+            _ => return Err(invalid(&Value::String(s))),
+        },
+        (CastType::Int, Value::Bool(b)) => Value::from(i64::from(b)),
+        (CastType::Int, Value::Number(n)) if n.is_i64() || n.is_u64() => Value::Number(n),
+        // 浮点数只接受没有小数部分的
+        (CastType::Int, Value::Number(n)) => match n.as_f64() {
+            Some(f) if f.fract() == 0.0 && f.abs() < i64::MAX as f64 => Value::from(f as i64),
+            _ => return Err(invalid(&Value::Number(n))),
+        },
+        (CastType::Int, Value::String(s)) => match s.trim().parse::<i64>() {
+            Ok(int) => Value::from(int),
+            Err(_) => return Err(invalid(&Value::String(s))),
+        },
+        (CastType::Float, Value::Bool(b)) => Value::from(if b { 1.0 } else { 0.0 }),
+        (CastType::Float, Value::Number(n)) => n.as_f64().map(Value::from).ok_or_else(|| invalid(&Value::Number(n)))?,
+        (CastType::Float, Value::String(s)) => s.trim().parse::<f64>().ok()
+            .and_then(serde_json::Number::from_f64)
+            .map(Value::Number)
+            .ok_or_else(|| invalid(&Value::String(s)))?,
+        (CastType::String, Value::String(s)) => Value::String(s),
+        (CastType::String, value) => Value::String(value.to_string()),
+        (CastType::Json, Value::String(s)) => serde_json::from_str(&s)
+            .map_err(|e| format!("invalid JSON: {}", e))?,
+        (CastType::Json, value) => value,
+        (_, value) => return Err(invalid(&value)),
+    })
+}
+
+/// 按`_`、`-`、空格以及小写到大写的变化拆分单词，连续的大写字母（如`HTTPStatus`中的`HTTP`）视为一个单词
+fn words(name: &str) -> Vec<String> {
+    let chars: Vec<char> = name.chars().collect();
+    let mut words = Vec::new();
+    let mut current = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if c == '_' || c == '-' || c.is_whitespace() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        if c.is_uppercase() && !current.is_empty() {
+            let previous = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).is_some_and(|next| next.is_lowercase());
+            if previous.is_lowercase() || previous.is_ascii_digit() || (previous.is_uppercase() && next_is_lower) {
+                words.push(std::mem::take(&mut current));
+            }
+        }
+        current.push(c);
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+pub fn convert_case(name: &str, case: RenameCase) -> String {
+    let words = words(name);
+    let capitalize = |word: &str| {
+        let mut chars = word.chars();
+        chars.next().map_or(String::new(), |first| first.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect())
+    };
+    match case {
+        RenameCase::Camel => words.iter().enumerate()
+            .map(|(i, word)| if i == 0 { word.to_lowercase() } else { capitalize(word) })
+            .collect(),
+        RenameCase::Pascal => words.iter().map(|word| capitalize(word)).collect(),
+        RenameCase::Snake => words.iter().map(|word| word.to_lowercase()).collect::<Vec<_>>().join("_"),
+        RenameCase::Kebab => words.iter().map(|word| word.to_lowercase()).collect::<Vec<_>>().join("-"),
+        RenameCase::ScreamingSnake => words.iter().map(|word| word.to_uppercase()).collect::<Vec<_>>().join("_"),
+    }
+}